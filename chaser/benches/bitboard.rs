@@ -0,0 +1,76 @@
+use std::{collections::VecDeque, hint::black_box};
+
+use chaser::{
+    bitboard::Bitboard,
+    game_types::{Element, Map, Pos},
+};
+use criterion::{Criterion, criterion_group, criterion_main};
+
+/// A 15x17 board with a fixed scatter of walls, roughly as dense as a real one.
+fn board() -> Map {
+    let mut map = Map::empty((15, 17));
+    let mut seed = 0x2545_f491_u32;
+    for pos in map.positions().collect::<Vec<_>>() {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        if seed.is_multiple_of(5) {
+            map.set(pos, Element::Wall);
+        }
+    }
+    map.set(Pos::new(0, 0), Element::Cold);
+    map.set(Pos::new(14, 16), Element::Blank);
+    map
+}
+
+fn bfs_around_4(map: &Map, from: Pos, to: Pos) -> Option<usize> {
+    let mut dist = vec![vec![None; map.width()]; map.height()];
+    let mut queue = VecDeque::from([from]);
+    dist[from.y][from.x] = Some(0);
+    while let Some(pos) = queue.pop_front() {
+        let d = dist[pos.y][pos.x].unwrap();
+        if pos == to {
+            return Some(d);
+        }
+        for (elem, n, _) in map.around_4(pos) {
+            if elem != Element::Wall && dist[n.y][n.x].is_none() {
+                dist[n.y][n.x] = Some(d + 1);
+                queue.push_back(n);
+            }
+        }
+    }
+    None
+}
+
+fn bfs(c: &mut Criterion) {
+    let map = board();
+    let bits = Bitboard::from_map(&map).unwrap();
+    let (from, to) = (Pos::new(0, 0), Pos::new(14, 16));
+    assert_eq!(bfs_around_4(&map, from, to), bits.distance(from, to));
+
+    let mut group = c.benchmark_group("bfs corner to corner");
+    group.bench_function("around_4", |b| {
+        b.iter(|| bfs_around_4(black_box(&map), from, to))
+    });
+    group.bench_function("bitboard", |b| {
+        b.iter(|| black_box(&bits).distance(from, to))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("flood fill");
+    group.bench_function("bitboard", |b| {
+        b.iter(|| {
+            let bits = black_box(&bits);
+            bits.flood_fill(bits.cell(from), bits.open())
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("copy board");
+    group.bench_function("Map::clone", |b| b.iter(|| black_box(&map).clone()));
+    group.bench_function("Bitboard", |b| b.iter(|| *black_box(&bits)));
+    group.finish();
+}
+
+criterion_group!(benches, bfs);
+criterion_main!(benches);
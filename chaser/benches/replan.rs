@@ -0,0 +1,67 @@
+use std::hint::black_box;
+
+use chaser::{
+    dstar::DStarLite,
+    game_types::{Direction, Element, Map, Pos},
+    nav::{Nav, UnknownCells, follow},
+};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+/// Walks from one corner to the other while a wall appears two steps ahead every turn,
+/// asking `plan` for a new path each time. Returns the number of steps taken.
+fn walk(size: (usize, usize), mut plan: impl FnMut(&Map, Pos) -> Option<Vec<Direction>>) -> usize {
+    let mut map = Map::empty(size);
+    let goal = Pos::new(size.0 - 1, size.1 - 1);
+    let mut us = Pos::new(0, 0);
+    let mut steps = 0;
+    while us != goal {
+        let Some(path) = plan(&map, us) else {
+            break;
+        };
+        if path.len() > 2 {
+            map.set(follow(us, &path[..2]).unwrap(), Element::Wall);
+        }
+        let Some(path) = plan(&map, us) else {
+            break;
+        };
+        us = follow(us, &path[..1]).unwrap();
+        steps += 1;
+    }
+    steps
+}
+
+fn replan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("walk while walls appear");
+    for size in [(15, 17), (45, 51)] {
+        let name = format!("{}x{}", size.0, size.1);
+        group.bench_with_input(BenchmarkId::new("fresh A*", &name), &size, |b, &size| {
+            b.iter(|| {
+                walk(black_box(size), |map, us| {
+                    Nav::new(map).path(us, Pos::new(size.0 - 1, size.1 - 1))
+                })
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("D* Lite", &name), &size, |b, &size| {
+            b.iter(|| {
+                let mut planner: Option<DStarLite> = None;
+                walk(black_box(size), |map, us| {
+                    let planner = planner.get_or_insert_with(|| {
+                        DStarLite::new(
+                            map,
+                            us,
+                            Pos::new(size.0 - 1, size.1 - 1),
+                            UnknownCells::Passable,
+                        )
+                    });
+                    planner.move_to(us);
+                    planner.update_map(map);
+                    planner.path()
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, replan);
+criterion_main!(benches);
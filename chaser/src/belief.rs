@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_types::{Element, Map, Pos},
+    grid::Grid,
+};
+
+/// Probability distribution over where the opponent could be standing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpponentBelief {
+    prob: Grid<f64>,
+}
+impl OpponentBelief {
+    /// Spreads the mass evenly over every cell of `map` that isn't a known wall.
+    pub fn uniform(map: &Map) -> OpponentBelief {
+        let mut belief = OpponentBelief {
+            prob: Grid::new(map.size(), 0.0),
+        };
+        belief.reset(map);
+        belief
+    }
+    pub fn certain(pos: Pos, size: (usize, usize)) -> OpponentBelief {
+        let mut belief = OpponentBelief {
+            prob: Grid::new(size, 0.0),
+        };
+        belief.collapse(pos);
+        belief
+    }
+
+    pub fn at(&self, pos: Pos) -> f64 {
+        self.prob.get(pos).copied().unwrap_or(0.0)
+    }
+    pub fn grid(&self) -> &Grid<f64> {
+        &self.prob
+    }
+
+    /// Advances the belief by one opponent turn: they either stay or step into an open neighbour.
+    pub fn propagate(&mut self, map: &Map) {
+        let mut next = Grid::new(self.prob.size(), 0.0);
+        for (pos, &p) in self.prob.iter() {
+            if p == 0.0 {
+                continue;
+            }
+            let mut options = map.around_4(pos);
+            options.retain(|(elem, _, _)| *elem != Element::Wall);
+            let share = p / (options.len() + 1) as f64;
+            next[pos] += share;
+            for (_, n, _) in options {
+                next[n] += share;
+            }
+        }
+        self.prob = next;
+    }
+
+    /// A scan saw `pos` without the opponent in it. Call [`OpponentBelief::normalize`] once
+    /// the whole scan has been applied.
+    pub fn exclude(&mut self, pos: Pos) {
+        self.prob.set(pos, 0.0);
+    }
+    /// Zeroes every cell `keep` rejects. Like [`OpponentBelief::exclude`], follow up with
+    /// [`OpponentBelief::normalize`].
+    pub fn restrict(&mut self, keep: impl Fn(Pos) -> bool) {
+        for (pos, p) in self.prob.iter_mut() {
+            if !keep(pos) {
+                *p = 0.0;
+            }
+        }
+    }
+    /// The opponent was seen at `pos`.
+    pub fn collapse(&mut self, pos: Pos) {
+        self.prob.fill(0.0);
+        self.prob.set(pos, 1.0);
+    }
+    /// Rescales the mass back to 1, starting over from [`OpponentBelief::uniform`] if the
+    /// observations ruled out every cell.
+    pub fn normalize(&mut self, map: &Map) {
+        let total = self.total();
+        if total <= f64::EPSILON {
+            self.reset(map);
+            return;
+        }
+        for p in self.prob.values_mut() {
+            *p /= total;
+        }
+    }
+    fn reset(&mut self, map: &Map) {
+        let mut open = 0;
+        for (pos, p) in self.prob.iter_mut() {
+            *p = if map.at(pos) == Element::Wall {
+                0.0
+            } else {
+                open += 1;
+                1.0
+            };
+        }
+        if open > 0 {
+            for p in self.prob.values_mut() {
+                *p /= open as f64;
+            }
+        }
+    }
+
+    pub fn total(&self) -> f64 {
+        self.prob.values().sum()
+    }
+    /// Cells with any probability, most likely first.
+    pub fn most_likely(&self) -> Vec<(Pos, f64)> {
+        let mut cells: Vec<_> = self
+            .prob
+            .iter()
+            .filter(|&(_, &p)| p > 0.0)
+            .map(|(pos, &p)| (pos, p))
+            .collect();
+        cells.sort_by(|a, b| b.1.total_cmp(&a.1));
+        cells
+    }
+    /// Probability mass inside the inclusive rectangle spanned by `from` and `to`.
+    pub fn mass_in(&self, from: Pos, to: Pos) -> f64 {
+        self.prob.region(from, to).map(|(_, &p)| p).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring() -> Map {
+        "...\n.#.\n...".parse().unwrap()
+    }
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn spreads_over_open_cells() {
+        let map = ring();
+        let belief = OpponentBelief::uniform(&map);
+        assert!(close(belief.at(Pos::new(0, 0)), 1.0 / 8.0));
+        assert_eq!(belief.at(Pos::new(1, 1)), 0.0);
+        assert_eq!(belief.at(Pos::new(3, 0)), 0.0);
+        assert!(close(belief.total(), 1.0));
+    }
+
+    #[test]
+    fn propagates_one_turn_at_a_time() {
+        let map = ring();
+        let mut belief = OpponentBelief::certain(Pos::new(0, 0), map.size());
+        belief.propagate(&map);
+        // stay or take one of two steps, the wall in the middle isn't one
+        for pos in [Pos::new(0, 0), Pos::new(1, 0), Pos::new(0, 1)] {
+            assert!(close(belief.at(pos), 1.0 / 3.0));
+        }
+        belief.propagate(&map);
+        assert!(close(belief.total(), 1.0));
+        assert_eq!(belief.most_likely()[0].0, Pos::new(0, 0));
+        assert!(close(belief.at(Pos::new(0, 0)), 1.0 / 3.0));
+        assert!(close(belief.at(Pos::new(1, 0)), 2.0 / 9.0));
+        assert!(close(belief.at(Pos::new(2, 0)), 1.0 / 9.0));
+        assert!(close(
+            belief.mass_in(Pos::new(0, 0), Pos::new(2, 0)),
+            2.0 / 3.0
+        ));
+        assert_eq!(belief.most_likely().len(), 5);
+    }
+
+    #[test]
+    fn excludes_and_starts_over_when_ruled_out() {
+        let map = ring();
+        let mut belief = OpponentBelief::certain(Pos::new(0, 0), map.size());
+        belief.propagate(&map);
+        belief.exclude(Pos::new(0, 0));
+        belief.normalize(&map);
+        assert!(close(belief.at(Pos::new(1, 0)), 0.5));
+        assert!(close(belief.at(Pos::new(0, 1)), 0.5));
+
+        belief.restrict(|pos| pos.y == 2);
+        belief.normalize(&map);
+        // seen everywhere they could have been, so anywhere open again
+        assert!(close(belief.at(Pos::new(2, 2)), 1.0 / 8.0));
+        assert!(close(belief.total(), 1.0));
+    }
+}
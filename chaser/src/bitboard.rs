@@ -0,0 +1,374 @@
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+
+use crate::{
+    game_types::{Element, Map, Pos, Side},
+    grid::Grid,
+};
+
+const WORDS: usize = 8;
+/// Largest board a [`Bitboard`] can hold, the real one is 15x17 = 255 cells.
+pub const MAX_CELLS: usize = WORDS * 64;
+
+/// Fixed-size set of cell indices, `y * width + x`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BitSet([u64; WORDS]);
+impl BitSet {
+    pub const EMPTY: BitSet = BitSet([0; WORDS]);
+
+    /// The first `n` indices.
+    pub fn first(n: usize) -> BitSet {
+        let mut set = BitSet::EMPTY;
+        for (i, word) in set.0.iter_mut().enumerate() {
+            let bits = n.saturating_sub(i * 64).min(64);
+            *word = if bits == 64 {
+                u64::MAX
+            } else {
+                (1 << bits) - 1
+            };
+        }
+        set
+    }
+    pub fn single(i: usize) -> BitSet {
+        let mut set = BitSet::EMPTY;
+        set.insert(i);
+        set
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        i < MAX_CELLS && self.0[i / 64] & (1 << (i % 64)) != 0
+    }
+    pub fn insert(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+    pub fn remove(&mut self, i: usize) {
+        self.0[i / 64] &= !(1 << (i % 64));
+    }
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&w| w == 0)
+    }
+    pub fn intersects(&self, other: BitSet) -> bool {
+        !(*self & other).is_empty()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = usize> + use<> {
+        let words = self.0;
+        (0..WORDS).flat_map(move |i| {
+            let mut word = words[i];
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+
+    /// Moves every index up by `n`, dropping the ones that fall off the end.
+    pub fn shift_up(self, n: usize) -> BitSet {
+        let (words, bits) = (n / 64, n % 64);
+        let mut out = BitSet::EMPTY;
+        for i in (words..WORDS).rev() {
+            let src = i - words;
+            out.0[i] = self.0[src] << bits;
+            if bits > 0 && src > 0 {
+                out.0[i] |= self.0[src - 1] >> (64 - bits);
+            }
+        }
+        out
+    }
+    /// Moves every index down by `n`, dropping the ones that go below zero.
+    pub fn shift_down(self, n: usize) -> BitSet {
+        let (words, bits) = (n / 64, n % 64);
+        let mut out = BitSet::EMPTY;
+        for i in 0..WORDS.saturating_sub(words) {
+            let src = i + words;
+            out.0[i] = self.0[src] >> bits;
+            if bits > 0 && src + 1 < WORDS {
+                out.0[i] |= self.0[src + 1] << (64 - bits);
+            }
+        }
+        out
+    }
+}
+impl BitAnd for BitSet {
+    type Output = BitSet;
+    fn bitand(mut self, rhs: BitSet) -> BitSet {
+        self &= rhs;
+        self
+    }
+}
+impl BitAndAssign for BitSet {
+    fn bitand_assign(&mut self, rhs: BitSet) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a &= b;
+        }
+    }
+}
+impl BitOr for BitSet {
+    type Output = BitSet;
+    fn bitor(mut self, rhs: BitSet) -> BitSet {
+        self |= rhs;
+        self
+    }
+}
+impl BitOrAssign for BitSet {
+    fn bitor_assign(&mut self, rhs: BitSet) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a |= b;
+        }
+    }
+}
+impl Not for BitSet {
+    type Output = BitSet;
+    fn not(self) -> BitSet {
+        BitSet(self.0.map(|w| !w))
+    }
+}
+
+/// A [`Map`] packed into bitsets, cheap to copy and to search. [`Sim`](crate::sim::Sim)
+/// plays out its searches on one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bitboard {
+    width: usize,
+    height: usize,
+    pub walls: BitSet,
+    pub hearts: BitSet,
+    /// Cells that aren't [`Element::Unknown`].
+    pub known: BitSet,
+    pub cold: Option<Pos>,
+    pub hot: Option<Pos>,
+    board: BitSet,
+    left_column: BitSet,
+    right_column: BitSet,
+}
+impl Bitboard {
+    /// A board of `size` where nothing is known yet. `None` if it has more than
+    /// [`MAX_CELLS`] cells.
+    pub fn new(size: (usize, usize)) -> Option<Bitboard> {
+        let (width, height) = size;
+        if width == 0 || width * height > MAX_CELLS {
+            return None;
+        }
+        let mut left_column = BitSet::EMPTY;
+        for y in 0..height {
+            left_column.insert(y * width);
+        }
+        Some(Bitboard {
+            width,
+            height,
+            walls: BitSet::EMPTY,
+            hearts: BitSet::EMPTY,
+            known: BitSet::EMPTY,
+            cold: None,
+            hot: None,
+            board: BitSet::first(width * height),
+            left_column,
+            right_column: left_column.shift_up(width - 1),
+        })
+    }
+    pub fn from_map(map: &Map) -> Option<Bitboard> {
+        let mut board = Bitboard::new(map.size())?;
+        for (pos, &elem) in map.iter() {
+            board.set(pos, elem);
+        }
+        Some(board)
+    }
+    pub fn to_map(&self) -> Map {
+        let mut map = Map::unknown(self.size());
+        for pos in map.positions() {
+            map.set(pos, self.at(pos));
+        }
+        map
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+    pub fn index(&self, pos: Pos) -> Option<usize> {
+        pos.in_bounds(self.size())
+            .then(|| pos.y * self.width + pos.x)
+    }
+    pub fn pos(&self, index: usize) -> Pos {
+        Pos::new(index % self.width, index / self.width)
+    }
+    /// Every cell on the board.
+    pub fn all(&self) -> BitSet {
+        self.board
+    }
+    pub fn cell(&self, pos: Pos) -> BitSet {
+        self.index(pos).map_or(BitSet::EMPTY, BitSet::single)
+    }
+    pub fn player(&self, side: Side) -> Option<Pos> {
+        match side {
+            Side::Cold => self.cold,
+            Side::Hot => self.hot,
+        }
+    }
+
+    pub fn at(&self, pos: Pos) -> Element {
+        let Some(i) = self.index(pos) else {
+            return Element::Unknown;
+        };
+        match (Some(pos) == self.cold, Some(pos) == self.hot) {
+            (true, true) => Element::BothColdAndHot,
+            (true, false) => Element::Cold,
+            (false, true) => Element::Hot,
+            _ if !self.known.contains(i) => Element::Unknown,
+            _ if self.walls.contains(i) => Element::Wall,
+            _ if self.hearts.contains(i) => Element::Heart,
+            _ => Element::Blank,
+        }
+    }
+    /// Like [`Map::set`], except that a player marker moves that player instead of adding a
+    /// second one.
+    pub fn set(&mut self, pos: Pos, elem: Element) -> bool {
+        let Some(i) = self.index(pos) else {
+            return false;
+        };
+        self.walls.remove(i);
+        self.hearts.remove(i);
+        if self.cold == Some(pos) {
+            self.cold = None;
+        }
+        if self.hot == Some(pos) {
+            self.hot = None;
+        }
+        if elem == Element::Unknown {
+            self.known.remove(i);
+        } else {
+            self.known.insert(i);
+        }
+        match elem {
+            Element::Wall => self.walls.insert(i),
+            Element::Heart => self.hearts.insert(i),
+            Element::Cold => self.cold = Some(pos),
+            Element::Hot => self.hot = Some(pos),
+            Element::BothColdAndHot => {
+                self.cold = Some(pos);
+                self.hot = Some(pos);
+            }
+            Element::Blank | Element::Unknown => {}
+        }
+        true
+    }
+
+    /// Cells that aren't known walls, unknown ones included.
+    pub fn open(&self) -> BitSet {
+        self.board & !self.walls
+    }
+    /// Every cell orthogonally adjacent to one in `set`.
+    pub fn neighbors(&self, set: BitSet) -> BitSet {
+        let right = set.shift_up(1) & !self.left_column;
+        let left = set.shift_down(1) & !self.right_column;
+        (right | left | set.shift_up(self.width) | set.shift_down(self.width)) & self.board
+    }
+    /// Everything reachable from `from` by walking through `passable`.
+    pub fn flood_fill(&self, from: BitSet, passable: BitSet) -> BitSet {
+        let mut filled = from & passable;
+        loop {
+            let next = filled | (self.neighbors(filled) & passable);
+            if next == filled {
+                return filled;
+            }
+            filled = next;
+        }
+    }
+    pub fn reachable(&self, from: Pos, to: Pos) -> bool {
+        self.flood_fill(self.cell(from), self.open())
+            .contains(self.index(to).unwrap_or(MAX_CELLS))
+    }
+    /// Length of the shortest walk from `from` to `to` around known walls.
+    pub fn distance(&self, from: Pos, to: Pos) -> Option<usize> {
+        let target = self.cell(to);
+        let passable = self.open();
+        let mut frontier = self.cell(from) & passable;
+        let mut seen = frontier;
+        for steps in 0.. {
+            if frontier.intersects(target) {
+                return Some(steps);
+            }
+            if frontier.is_empty() {
+                return None;
+            }
+            frontier = self.neighbors(frontier) & passable & !seen;
+            seen |= frontier;
+        }
+        unreachable!()
+    }
+    /// Walking distance from `from` to every reachable cell.
+    pub fn distances(&self, from: Pos) -> Grid<Option<usize>> {
+        let mut out = Grid::new(self.size(), None);
+        let passable = self.open();
+        let mut frontier = self.cell(from) & passable;
+        let mut seen = frontier;
+        let mut steps = 0;
+        while !frontier.is_empty() {
+            for i in frontier.iter() {
+                out.set(self.pos(i), Some(steps));
+            }
+            frontier = self.neighbors(frontier) & passable & !seen;
+            seen |= frontier;
+            steps += 1;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board() -> Map {
+        let mut map = Map::empty((15, 17));
+        for y in 0..16 {
+            map.set(Pos::new(7, y), Element::Wall);
+        }
+        map.set(Pos::new(3, 3), Element::Heart);
+        map.set(Pos::new(12, 0), Element::Unknown);
+        map.set(Pos::new(0, 0), Element::Cold);
+        map.set(Pos::new(14, 0), Element::Hot);
+        map
+    }
+
+    #[test]
+    fn round_trips_through_map() {
+        let map = board();
+        let bits = Bitboard::from_map(&map).unwrap();
+        let back = bits.to_map();
+        for (pos, &elem) in map.iter() {
+            assert_eq!(back.at(pos), elem, "at {pos}");
+        }
+        assert_eq!(bits.player(Side::Cold), Some(Pos::new(0, 0)));
+        assert!(Bitboard::new((40, 40)).is_none());
+    }
+
+    #[test]
+    fn neighbors_do_not_wrap_rows() {
+        let bits = Bitboard::from_map(&board()).unwrap();
+        let n = bits.neighbors(bits.cell(Pos::new(14, 3)));
+        let cells: Vec<_> = n.iter().map(|i| bits.pos(i)).collect();
+        assert_eq!(cells, [Pos::new(14, 2), Pos::new(13, 3), Pos::new(14, 4)]);
+        let n = bits.neighbors(bits.cell(Pos::new(0, 16)));
+        assert_eq!(n.len(), 2);
+    }
+
+    #[test]
+    fn walks_around_walls() {
+        let bits = Bitboard::from_map(&board()).unwrap();
+        // down the left half, through the gap on the last row and back up
+        assert_eq!(
+            bits.distance(Pos::new(0, 0), Pos::new(14, 0)),
+            Some(14 + 16 * 2)
+        );
+        assert_eq!(bits.distances(Pos::new(0, 0))[Pos::new(3, 3)], Some(6));
+
+        let mut sealed = bits;
+        sealed.set(Pos::new(7, 16), Element::Wall);
+        assert!(!sealed.reachable(Pos::new(0, 0), Pos::new(14, 0)));
+        assert_eq!(sealed.distance(Pos::new(0, 0), Pos::new(14, 0)), None);
+    }
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game_types::{Element, Map, Pos, Side};
+
+/// Something that happened on the board between two `updata_board` packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoardEvent {
+    PlayerMoved {
+        side: Side,
+        from: Pos,
+        to: Pos,
+    },
+    HeartCollected {
+        side: Side,
+        pos: Pos,
+    },
+    WallPlaced {
+        pos: Pos,
+    },
+    HeartAppeared {
+        pos: Pos,
+    },
+    /// A cell we had never seen, only happens under fog of war.
+    CellRevealed {
+        pos: Pos,
+        elem: Element,
+    },
+}
+
+fn sides_on(elem: Element) -> &'static [Side] {
+    match elem {
+        Element::Cold => &[Side::Cold],
+        Element::Hot => &[Side::Hot],
+        Element::BothColdAndHot => &[Side::Cold, Side::Hot],
+        _ => &[],
+    }
+}
+
+/// Events that turn `old` into `new`. Both maps must have the same size.
+pub fn diff(old: &Map, new: &Map) -> Vec<BoardEvent> {
+    let mut events = vec![];
+    for side in [Side::Cold, Side::Hot] {
+        if let (Some(from), Some(to)) = (old.find_player(side), new.find_player(side))
+            && from != to
+        {
+            events.push(BoardEvent::PlayerMoved { side, from, to });
+        }
+    }
+    for (pos, &from) in old.iter() {
+        let Some(to) = new.get(pos) else {
+            continue;
+        };
+        if from == to {
+            continue;
+        }
+        match (from, to) {
+            (Element::Unknown, _) => events.push(BoardEvent::CellRevealed { pos, elem: to }),
+            (Element::Heart, _) if !sides_on(to).is_empty() => {
+                for &side in sides_on(to) {
+                    events.push(BoardEvent::HeartCollected { side, pos });
+                }
+            }
+            (_, Element::Wall) => events.push(BoardEvent::WallPlaced { pos }),
+            (Element::Blank, Element::Heart) => events.push(BoardEvent::HeartAppeared { pos }),
+            _ => (),
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_what_changed() {
+        let old: Map = "C*.\n..*\n?.H".parse().unwrap();
+        let new: Map = ".C#\n.*H\n..#".parse().unwrap();
+        assert_eq!(
+            diff(&old, &new),
+            [
+                BoardEvent::PlayerMoved {
+                    side: Side::Cold,
+                    from: Pos::new(0, 0),
+                    to: Pos::new(1, 0),
+                },
+                BoardEvent::PlayerMoved {
+                    side: Side::Hot,
+                    from: Pos::new(2, 2),
+                    to: Pos::new(2, 1),
+                },
+                BoardEvent::HeartCollected {
+                    side: Side::Cold,
+                    pos: Pos::new(1, 0),
+                },
+                BoardEvent::WallPlaced {
+                    pos: Pos::new(2, 0),
+                },
+                BoardEvent::HeartAppeared {
+                    pos: Pos::new(1, 1),
+                },
+                BoardEvent::HeartCollected {
+                    side: Side::Hot,
+                    pos: Pos::new(2, 1),
+                },
+                BoardEvent::CellRevealed {
+                    pos: Pos::new(0, 2),
+                    elem: Element::Blank,
+                },
+                BoardEvent::WallPlaced {
+                    pos: Pos::new(2, 2),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::{
+    game_types::{Direction, Map, Pos},
+    grid::Grid,
+    nav::UnknownCells,
+};
+
+const INF: u32 = u32::MAX;
+type Key = (u32, u32);
+
+/// D* Lite planner towards a fixed goal. It searches backwards from the goal, so when we
+/// move or a few cells change, only the affected part of the search is redone.
+///
+/// Costs follow [`UnknownCells::enter_cost`], the same as [`crate::nav::Nav`] without a cost
+/// function, and [`DStarLite::path`] is always as cheap as a fresh [`crate::nav::Nav::path`].
+#[derive(Debug, Clone)]
+pub struct DStarLite {
+    map: Map,
+    unknown: UnknownCells,
+    start: Pos,
+    goal: Pos,
+    /// Offset added to keys queued before the start moved, instead of re-keying the queue.
+    km: u32,
+    /// Cost to the goal as of the last expansion.
+    g: Grid<u32>,
+    /// One-step lookahead cost to the goal.
+    rhs: Grid<u32>,
+    queue: BinaryHeap<Reverse<(Key, Pos)>>,
+    /// Current key of every queued cell, older heap entries for a cell are skipped.
+    queued: Grid<Option<Key>>,
+}
+impl DStarLite {
+    pub fn new(map: &Map, start: Pos, goal: Pos, unknown: UnknownCells) -> DStarLite {
+        let size = map.size();
+        let mut planner = DStarLite {
+            map: map.clone(),
+            unknown,
+            start,
+            goal,
+            km: 0,
+            g: Grid::new(size, INF),
+            rhs: Grid::new(size, INF),
+            queue: BinaryHeap::new(),
+            queued: Grid::new(size, None),
+        };
+        if planner.map.contains(goal) {
+            planner.rhs[goal] = 0;
+            planner.enqueue(goal);
+        }
+        planner
+    }
+    pub fn start(&self) -> Pos {
+        self.start
+    }
+    pub fn goal(&self) -> Pos {
+        self.goal
+    }
+
+    /// We walked to `start`.
+    pub fn move_to(&mut self, start: Pos) {
+        self.km += self.start.manhattan(start) as u32;
+        self.start = start;
+    }
+    /// Takes in the latest board, repairing the search around every cell whose cost changed.
+    /// Returns how many did.
+    pub fn update_map(&mut self, map: &Map) -> usize {
+        let changed: Vec<Pos> = map
+            .iter()
+            .filter(|&(pos, &elem)| {
+                self.unknown.enter_cost(elem) != self.unknown.enter_cost(self.map.at(pos))
+            })
+            .map(|(pos, _)| pos)
+            .collect();
+        self.map = map.clone();
+        for &pos in &changed {
+            self.cell_changed(pos);
+        }
+        changed.len()
+    }
+    fn cell_changed(&mut self, pos: Pos) {
+        // only the edges into `pos` got more or less expensive
+        for (n, _) in pos.neighbors(self.map.size()) {
+            self.update_vertex(n);
+        }
+    }
+
+    /// Cost of the cheapest path from the start to the goal.
+    pub fn cost(&mut self) -> Option<u32> {
+        if !self.map.contains(self.start) {
+            return None;
+        }
+        self.compute();
+        Some(self.g[self.start]).filter(|&g| g != INF)
+    }
+    /// The cheapest path from the start to the goal in walking order.
+    pub fn path(&mut self) -> Option<Vec<Direction>> {
+        self.cost()?;
+        let mut path = vec![];
+        let mut pos = self.start;
+        while pos != self.goal {
+            let (dir, next) = pos
+                .neighbors(self.map.size())
+                .filter_map(|(n, dir)| Some((dir, n, self.through(n)?)))
+                .min_by_key(|&(_, _, cost)| cost)
+                .map(|(dir, n, _)| (dir, n))?;
+            path.push(dir);
+            pos = next;
+        }
+        Some(path)
+    }
+
+    fn h(&self, pos: Pos) -> u32 {
+        self.start.manhattan(pos) as u32
+    }
+    fn key(&self, pos: Pos) -> Key {
+        let best = self.g[pos].min(self.rhs[pos]);
+        (
+            best.saturating_add(self.h(pos)).saturating_add(self.km),
+            best,
+        )
+    }
+    fn enqueue(&mut self, pos: Pos) {
+        let key = self.key(pos);
+        self.queued[pos] = Some(key);
+        self.queue.push(Reverse((key, pos)));
+    }
+    /// Drops heap entries that were superseded or removed.
+    fn top(&mut self) -> Option<(Key, Pos)> {
+        while let Some(&Reverse((key, pos))) = self.queue.peek() {
+            if self.queued[pos] == Some(key) {
+                return Some((key, pos));
+            }
+            self.queue.pop();
+        }
+        None
+    }
+    /// Cost of stepping onto `pos` and going on to the goal from there.
+    fn through(&self, pos: Pos) -> Option<u32> {
+        let step = self.unknown.enter_cost(self.map.at(pos))?;
+        Some(step.saturating_add(self.g[pos])).filter(|&c| c != INF)
+    }
+    fn update_vertex(&mut self, pos: Pos) {
+        if pos != self.goal {
+            self.rhs[pos] = pos
+                .neighbors(self.map.size())
+                .filter_map(|(n, _)| self.through(n))
+                .min()
+                .unwrap_or(INF);
+        }
+        self.queued[pos] = None;
+        if self.g[pos] != self.rhs[pos] {
+            self.enqueue(pos);
+        }
+    }
+    fn compute(&mut self) {
+        let start = self.start;
+        while let Some((old_key, pos)) = self.top() {
+            if old_key >= self.key(start) && self.rhs[start] == self.g[start] {
+                break;
+            }
+            let new_key = self.key(pos);
+            if old_key < new_key {
+                self.enqueue(pos);
+                continue;
+            }
+            self.queue.pop();
+            self.queued[pos] = None;
+            if self.g[pos] > self.rhs[pos] {
+                self.g[pos] = self.rhs[pos];
+            } else {
+                self.g[pos] = INF;
+                self.update_vertex(pos);
+            }
+            for (n, _) in pos.neighbors(self.map.size()) {
+                self.update_vertex(n);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game_types::Element,
+        nav::{Nav, follow},
+    };
+
+    fn check(planner: &mut DStarLite, map: &Map) {
+        let fresh = Nav::new(map).path(planner.start(), planner.goal());
+        let path = planner.path();
+        assert_eq!(path.as_ref().map(Vec::len), fresh.map(|p| p.len()));
+        if let Some(path) = path {
+            let end = follow(planner.start(), &path);
+            assert_eq!(end, Some(planner.goal()));
+        }
+    }
+
+    #[test]
+    fn matches_fresh_search_while_walls_appear() {
+        let mut map = Map::empty((15, 17));
+        let goal = Pos::new(14, 16);
+        let mut us = Pos::new(0, 0);
+        let mut planner = DStarLite::new(&map, us, goal, UnknownCells::Passable);
+        let mut seed = 7u32;
+        while us != goal {
+            check(&mut planner, &map);
+            // a wall somewhere and one right on our path
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let pos = Pos::new(seed as usize / 7 % 15, seed as usize / 105 % 17);
+            if pos != goal && pos != us {
+                map.set(pos, Element::Wall);
+            }
+            let Some(path) = planner.path() else {
+                break;
+            };
+            if path.len() > 2 {
+                let blocked = follow(us, &path[..2]).unwrap();
+                map.set(blocked, Element::Wall);
+            }
+            planner.update_map(&map);
+            check(&mut planner, &map);
+
+            let Some(path) = planner.path() else {
+                break;
+            };
+            us = follow(us, &path[..1]).unwrap();
+            planner.move_to(us);
+        }
+    }
+
+    #[test]
+    fn notices_when_the_goal_is_sealed_off_and_reopened() {
+        let mut map: Map = "...\n.#.\n...".parse().unwrap();
+        let mut planner =
+            DStarLite::new(&map, Pos::new(0, 0), Pos::new(2, 2), UnknownCells::Blocked);
+        assert_eq!(planner.cost(), Some(4));
+        map.set(Pos::new(2, 1), Element::Wall);
+        map.set(Pos::new(1, 2), Element::Unknown);
+        planner.update_map(&map);
+        assert_eq!(planner.path(), None);
+        map.set(Pos::new(1, 2), Element::Blank);
+        assert_eq!(planner.update_map(&map), 1);
+        check(&mut planner, &map);
+    }
+}
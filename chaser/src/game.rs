@@ -1,32 +1,530 @@
-use parking_lot::{Mutex, MutexGuard};
-use std::{cmp::min, ffi::OsStr, sync::Arc, thread, time::Duration};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsStr,
+    fmt::{self, Display},
+    str::FromStr,
+    sync::{Arc, mpsc::Receiver},
+    thread,
+    time::Duration,
+};
 
 use crate::{
+    belief::OpponentBelief,
     client::{Client, SocketIo},
-    game_types::{Direction, Effect, Element, GameData, Map, RecElement, Side},
+    diff::{self, BoardEvent},
+    game_types::{
+        Direction, Effect, Element, GameData, Map, Pos, RecElement, ScanShape, SearchType, Side,
+    },
+    history::{Action, History, TurnEvent},
+    inference::{self, OpponentAction},
+    knowledge::{self, ChangeCause, LastSeen, MapChange, Sighting},
     packets::{C2SPacket, S2CPacket},
+    shared::SharedState,
     ui,
+    validate::{Report, Validator},
 };
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GamePhase {
     Starting,
     Turn(Side),
     Ended { winner: Side, reason: String },
 }
+impl Display for GamePhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GamePhase::Starting => write!(f, "starting"),
+            GamePhase::Turn(side) => write!(f, "{}'s turn", String::from(*side)),
+            GamePhase::Ended { winner, reason } => {
+                write!(f, "{} won ({reason})", String::from(*winner))
+            }
+        }
+    }
+}
+/// How much of the board the server is expected to reveal in this match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FogOfWar {
+    /// Decide from whether `new_board` contains the opponent.
+    #[default]
+    Auto,
+    Enabled,
+    Disabled,
+}
+impl FromStr for FogOfWar {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(FogOfWar::Auto),
+            "on" | "true" => Ok(FogOfWar::Enabled),
+            "off" | "false" => Ok(FogOfWar::Disabled),
+            e => Err(format!(
+                "unknown fog of war mode {e} (expected auto, on or off)"
+            )),
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameState {
     pub room: String,
     pub phase: GamePhase,
     pub map: Map,
+    pub last_seen: LastSeen,
     pub map_size: (usize, usize),
     pub effect: Option<Effect>,
     pub turns_left: u32,
     pub players: Players,
+    pub opponent_belief: OpponentBelief,
+    /// What the opponent did according to the latest `updata_board`.
+    pub inferred: Vec<OpponentAction>,
+    /// Set once an inference already accounted for the opponent's last turn, so the belief
+    /// isn't propagated twice.
+    opponent_turn_resolved: bool,
+    /// Cells we found changed since our last `GetReady`, such as walls the opponent placed.
+    pub changes: Vec<MapChange>,
+    /// What our last action should have done, until the server confirms it.
+    pub pending: Option<Prediction>,
+    /// Every time the server disagreed with one of our predictions.
+    pub desyncs: Vec<Desync>,
+    /// Whether the opponent is only known through our own scans.
+    pub fog_of_war: bool,
+    /// Tracker accuracy so far, when running with `VALIDATE` set.
+    pub validation: Option<Report>,
+    pub history: History,
+    /// What changed on the board with the latest `updata_board`.
+    pub board_events: Vec<BoardEvent>,
+    /// Direction of the `Look` or `Search` whose result we are waiting for.
+    last_search: Option<Direction>,
+    /// A `move_rec` that arrived before the board confirmed where we moved to.
+    deferred_scan: Option<Vec<RecElement>>,
+}
+/// The optimistic effect of an action we sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Prediction {
+    Move { from: Pos, to: Pos },
+    Wall { pos: Pos },
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Desync {
+    Move { predicted: Pos, actual: Pos },
+    Wall { pos: Pos, actual: Element },
+}
+impl GameState {
+    /// Sets up tracking from the `new_board` we received. Under fog of war `map_data` is
+    /// ignored and the opponent starts out unknown.
+    pub fn new(
+        room: String,
+        map_size: (usize, usize),
+        map_data: &Map,
+        mut players: Players,
+        turns_left: u32,
+        fog_of_war: bool,
+    ) -> GameState {
+        if fog_of_war {
+            players.opponent.pos = None;
+        }
+        let map = if fog_of_war {
+            Map::unknown(map_size)
+        } else {
+            map_data.clone()
+        };
+        let opponent_belief = match players.opponent.pos {
+            Some(pos) => OpponentBelief::certain(pos, map_size),
+            None => OpponentBelief::uniform(&map),
+        };
+        let mut history = History::default();
+        history.push(turns_left, TurnEvent::Board(Arc::new(map.clone())));
+        GameState {
+            room,
+            phase: GamePhase::Starting,
+            map,
+            last_seen: LastSeen::new(map_size),
+            map_size,
+            effect: None,
+            turns_left,
+            players,
+            opponent_belief,
+            inferred: vec![],
+            opponent_turn_resolved: false,
+            changes: vec![],
+            pending: None,
+            desyncs: vec![],
+            fog_of_war,
+            validation: None,
+            history,
+            board_events: vec![],
+            last_search: None,
+            deferred_scan: None,
+        }
+    }
+
+    /// Updates the tracked state from a server packet.
+    pub fn on_packet(&mut self, packet: S2CPacket) {
+        match packet {
+            S2CPacket::GameResult { winner, info } => {
+                self.phase = GamePhase::Ended {
+                    winner,
+                    reason: info,
+                }
+            }
+            S2CPacket::UpdateBoard(GameData {
+                map_data,
+                cool_score,
+                hot_score,
+                turn,
+                effect,
+            }) => {
+                if let Some(Effect { player, .. }) = effect {
+                    self.phase = GamePhase::Turn(player);
+                }
+
+                if self.fog_of_war {
+                    // our marker is still where we were before any unconfirmed move
+                    let us = match self.pending {
+                        Some(Prediction::Move { from, .. }) => from,
+                        _ => self.players.us.pos,
+                    };
+                    let us_side = self.players.us.side;
+                    let new_us = map_data
+                        .find_player(self.players.us.side)
+                        .expect("player moved >2 blocks or was killed");
+                    self.map.set(us, Element::Blank);
+                    self.confirm_move(new_us);
+                    self.map.set(new_us, us_side.to_elem());
+                } else {
+                    let new_us = map_data.find_player(self.players.us.side);
+                    let new_opp = map_data.find_player(self.players.opponent.side);
+                    if let Some(Prediction::Wall { pos }) = self.pending {
+                        _ = self.confirm_wall(pos, map_data.at(pos));
+                    }
+                    self.map = map_data;
+                    if let Some(new_us) = new_us {
+                        self.confirm_move(new_us);
+                    }
+                    if let Some(new_opp) = new_opp {
+                        self.players.opponent.pos = Some(new_opp);
+                        self.opponent_belief.collapse(new_opp);
+                        self.history.push(turn, TurnEvent::OpponentSeen(new_opp));
+                    }
+                }
+                if let Some(rec_data) = self.deferred_scan.take() {
+                    self.scan(rec_data, ScanShape::Around);
+                }
+                self.turns_left = turn;
+                if !self.fog_of_war {
+                    self.last_seen.mark_all(Sighting {
+                        turn,
+                        scan: None,
+                        changed: false,
+                    });
+                }
+                self.effect = effect;
+                if let Some(effect) = effect
+                    && effect.player == self.players.opponent.side
+                {
+                    self.history.push(turn, TurnEvent::OpponentEffect(effect));
+                }
+                let old_scores = (self.players.us.score, self.players.opponent.score);
+                self.players.assign_scores(cool_score, hot_score);
+                let scores = (self.players.us.score, self.players.opponent.score);
+                if scores != old_scores {
+                    self.history.push(
+                        turn,
+                        TurnEvent::Scores {
+                            us: scores.0,
+                            opponent: scores.1,
+                        },
+                    );
+                }
+                self.inferred = inference::infer(
+                    self.players.opponent.side,
+                    old_scores.1,
+                    self.players.opponent.score,
+                    effect,
+                );
+                for action in self.inferred.clone() {
+                    self.apply_inference(action);
+                }
+                self.board_events = match self.history.last_board() {
+                    Some(prev) => diff::diff(prev, &self.map),
+                    None => vec![],
+                };
+                for &event in &self.board_events {
+                    self.history.push(turn, TurnEvent::BoardChanged(event));
+                }
+                self.history
+                    .push(turn, TurnEvent::Board(Arc::new(self.map.clone())));
+            }
+            S2CPacket::GetReadyRec { rec_data } => {
+                if self.fog_of_war && !self.opponent_turn_resolved {
+                    // the opponent has had one turn since our last scan
+                    self.opponent_belief.propagate(&self.map);
+                }
+                self.opponent_turn_resolved = false;
+                self.changes.clear();
+                self.scan(rec_data, ScanShape::Around);
+            }
+            S2CPacket::MoveRec { rec_data } => {
+                if let Some(Prediction::Move { .. }) = self.pending {
+                    _ = self.deferred_scan.insert(rec_data);
+                } else {
+                    self.scan(rec_data, ScanShape::Around);
+                }
+                _ = self.last_search.take();
+            }
+            S2CPacket::PutRec { rec_data } => {
+                self.scan(rec_data, ScanShape::Around);
+                _ = self.last_search.take();
+            }
+            S2CPacket::LookRec { rec_data } => {
+                if let Some(dir) = self.last_search.take() {
+                    self.scan(rec_data, ScanShape::Look(dir));
+                }
+            }
+            S2CPacket::SearchRec { rec_data } => {
+                if let Some(dir) = self.last_search.take() {
+                    self.scan(rec_data, ScanShape::Search(dir));
+                }
+            }
+            _ => (),
+        }
+    }
+    /// Records an action we are about to send, see [`GameState::on_packet`] for the answer.
+    pub fn on_sent(&mut self, packet: &C2SPacket) {
+        if let Some(action) = Action::from_packet(packet) {
+            self.history.push(self.turns_left, TurnEvent::Sent(action));
+        }
+        if let C2SPacket::Look(dir) | C2SPacket::Search(dir) = *packet {
+            _ = self.last_search.insert(dir);
+        } else {
+            _ = self.last_search.take();
+        }
+        self.predict(packet);
+    }
+
+    /// Writes a scanned cell into the map and records when we saw it.
+    fn observe(&mut self, pos: Pos, elem: Element, scan: SearchType) -> bool {
+        if !pos.in_bounds(self.map_size) {
+            return false;
+        }
+        let predicted = self.confirm_wall(pos, elem);
+        let changed = !predicted && knowledge::is_board_change(self.map.at(pos), elem);
+        if changed {
+            self.changes.push(MapChange {
+                pos,
+                from: self.map.at(pos),
+                to: elem,
+                cause: ChangeCause::Scan,
+            });
+        }
+        let sighting = Sighting {
+            turn: self.turns_left,
+            scan: Some(scan),
+            changed,
+        };
+        if self.fog_of_war
+            && elem != self.players.opponent.side.to_elem()
+            && elem != Element::BothColdAndHot
+        {
+            self.opponent_belief.exclude(pos);
+        }
+        self.map.set(pos, elem) && self.last_seen.mark(pos, sighting)
+    }
+    fn apply_inference(&mut self, action: OpponentAction) {
+        if !self.fog_of_war {
+            return;
+        }
+        match action {
+            OpponentAction::PickedHeart { .. } => {
+                if !self.opponent_turn_resolved {
+                    self.opponent_belief.propagate(&self.map);
+                }
+                // on a heart we knew of, or one we never saw
+                let map = &self.map;
+                self.opponent_belief
+                    .restrict(|pos| matches!(map.at(pos), Element::Heart | Element::Unknown));
+                self.opponent_belief.normalize(&self.map);
+                if let [(heart, _)] = self.opponent_belief.most_likely()[..] {
+                    let opp_elem = self.players.opponent.side.to_elem();
+                    self.map.set(heart, opp_elem);
+                    self.track_opponent(Some(heart));
+                }
+            }
+            OpponentAction::Scanned { .. } => (),
+        }
+        self.opponent_turn_resolved = true;
+    }
+    /// Writes the `rec_data` of a scan we made into the map.
+    fn scan(&mut self, rec_data: Vec<RecElement>, shape: ScanShape) {
+        let pos = self.players.us.pos;
+        let side = self.players.us.side;
+        let scan = shape.search_type();
+
+        let mut opp = None;
+        let mut cells = vec![];
+        for (elem, cell) in rec_data.into_iter().zip(shape.cells(pos, self.map_size)) {
+            let Some(cell) = cell else {
+                continue;
+            };
+            let elem = if cell == pos {
+                if matches!(elem, RecElement::Opponent) {
+                    Element::BothColdAndHot
+                } else {
+                    side.to_elem()
+                }
+            } else {
+                if matches!(elem, RecElement::Opponent) {
+                    _ = opp.insert(cell);
+                }
+                elem.into_elem(side)
+            };
+            _ = self.observe(cell, elem, scan);
+            cells.push((cell, elem));
+        }
+        self.history.push(
+            self.turns_left,
+            TurnEvent::Observed {
+                from: pos,
+                shape,
+                cells,
+            },
+        );
+
+        self.track_opponent(opp);
+    }
+
+    /// Applies what `packet` should do to our own state before the server answers.
+    fn predict(&mut self, packet: &C2SPacket) {
+        let (C2SPacket::MovePlayer(dir) | C2SPacket::PutWall(dir)) = *packet else {
+            return;
+        };
+        let from = self.players.us.pos;
+        let target = from.checked_step(dir, self.map_size);
+
+        if let C2SPacket::MovePlayer(_) = packet {
+            let to = target
+                .filter(|&to| self.map.at(to) != Element::Wall)
+                .unwrap_or(from);
+            self.players.us.pos = to;
+            self.pending = Some(Prediction::Move { from, to });
+        } else if let Some(pos) = target {
+            self.map.set(pos, Element::Wall);
+            self.pending = Some(Prediction::Wall { pos });
+        }
+    }
+    /// Checks a pending `MovePlayer` against where the server says we are.
+    fn confirm_move(&mut self, actual: Pos) {
+        self.players.us.pos = actual;
+        let Some(Prediction::Move { from, to }) = self.pending else {
+            return;
+        };
+        self.pending = None;
+        if actual == to {
+            return;
+        }
+        self.desync(Desync::Move {
+            predicted: to,
+            actual,
+        });
+        if actual == from && self.map.at(to) != Element::Wall {
+            // something was in the way that we didn't know about
+            self.changes.push(MapChange {
+                pos: to,
+                from: self.map.at(to),
+                to: Element::Wall,
+                cause: ChangeCause::BlockedMove,
+            });
+            self.map.set(to, Element::Wall);
+        }
+    }
+    /// Checks a pending `PutWall` against what the server says is at `pos`. Returns whether
+    /// `pos` was the predicted wall.
+    fn confirm_wall(&mut self, pos: Pos, actual: Element) -> bool {
+        let Some(Prediction::Wall { pos: predicted }) = self.pending else {
+            return false;
+        };
+        if pos != predicted {
+            return false;
+        }
+        self.pending = None;
+        if actual != Element::Wall {
+            self.desync(Desync::Wall { pos, actual });
+        }
+        true
+    }
+    fn desync(&mut self, desync: Desync) {
+        eprintln!("desync with the server: {desync:?}");
+        self.desyncs.push(desync);
+    }
+    /// Moves the tracked opponent to `sighting`, or forgets it if a scan covered its last known
+    /// cell without finding it. Only used under fog of war, full-information boards already
+    /// carry the opponent.
+    fn track_opponent(&mut self, sighting: Option<Pos>) {
+        if !self.fog_of_war {
+            return;
+        }
+        if let Some(opp) = sighting {
+            self.history
+                .push(self.turns_left, TurnEvent::OpponentSeen(opp));
+            self.opponent_belief.collapse(opp);
+            if let Some(old) = self.players.opponent.pos.replace(opp)
+                && old != opp
+            {
+                self.map.set(old, Element::Blank);
+            }
+        } else {
+            self.opponent_belief.normalize(&self.map);
+            if let Some(old) = self.players.opponent.pos
+                && self.opponent_belief.at(old) == 0.0
+            {
+                self.players.opponent.pos = None;
+                self.map.set(old, Element::Blank);
+            }
+        }
+    }
+}
+/// Short summary followed by the board, `{:#}` keeps the board pure ASCII.
+impl Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Players { us, opponent } = &self.players;
+        writeln!(
+            f,
+            "{}: {}, {} turns left",
+            self.room, self.phase, self.turns_left
+        )?;
+        writeln!(
+            f,
+            "{} ({}) at {}, {} points",
+            us.name,
+            String::from(us.side),
+            us.pos,
+            us.score
+        )?;
+        match opponent.pos {
+            Some(pos) => write!(
+                f,
+                "{} ({}) at {pos}",
+                opponent.name,
+                String::from(opponent.side)
+            )?,
+            None => write!(
+                f,
+                "{} ({}) unseen",
+                opponent.name,
+                String::from(opponent.side)
+            )?,
+        }
+        writeln!(f, ", {} points", opponent.score)?;
+        if f.alternate() {
+            write!(f, "{:#}", self.map)
+        } else {
+            write!(f, "{}", self.map)
+        }
+    }
 }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Players {
-    #[cfg(feature = "fog_of_war")]
     pub us: OwnPlayer,
-    #[cfg(not(feature = "fog_of_war"))]
-    pub us: Player,
     pub opponent: Player,
 }
 impl Players {
@@ -40,23 +538,20 @@ impl Players {
         }
     }
 }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Player {
     pub name: String,
-    #[cfg(feature = "fog_of_war")]
-    pub pos: Option<(usize, usize)>,
-    #[cfg(not(feature = "fog_of_war"))]
-    pub pos: (usize, usize),
+    pub pos: Option<Pos>,
     pub score: u32,
     pub side: Side,
 }
-#[cfg(feature = "fog_of_war")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OwnPlayer {
     pub name: String,
-    pub pos: (usize, usize),
+    pub pos: Pos,
     pub score: u32,
     pub side: Side,
 }
-#[cfg(feature = "fog_of_war")]
 impl From<OwnPlayer> for Player {
     fn from(v: OwnPlayer) -> Self {
         Self {
@@ -70,10 +565,12 @@ impl From<OwnPlayer> for Player {
 
 pub struct ChaserGame {
     client: Client,
-    state: Arc<Mutex<GameState>>,
+    /// Only the protocol thread writes to this, readers see it through `shared`.
+    state: GameState,
+    shared: Arc<SharedState>,
 }
 pub struct ChaserHandle {
-    state: Arc<Mutex<GameState>>,
+    state: Arc<SharedState>,
     send: Arc<Mutex<Option<C2SPacket>>>,
 }
 impl ChaserGame {
@@ -84,6 +581,14 @@ impl ChaserGame {
         url: impl AsRef<OsStr>,
         name: impl ToString,
         map: impl ToString,
+    ) -> ChaserHandle {
+        Self::join_with(url, name, map, FogOfWar::Auto)
+    }
+    pub fn join_with(
+        url: impl AsRef<OsStr>,
+        name: impl ToString,
+        map: impl ToString,
+        fog_of_war: FogOfWar,
     ) -> ChaserHandle {
         let name = name.to_string();
         let map = map.to_string();
@@ -133,78 +638,71 @@ impl ChaserGame {
 
         let cool_pos = map_data.find_player(Side::Cold);
         let hot_pos = map_data.find_player(Side::Hot);
-        let players = if cool_name == name {
-            Players {
-                #[cfg(feature = "fog_of_war")]
-                us: OwnPlayer {
-                    name: cool_name,
-                    pos: cool_pos.expect("!cool_pos"),
-                    score: cool_score,
-                    side: Side::Cold,
-                },
-                #[cfg(not(feature = "fog_of_war"))]
-                us: Player {
+        let (us, opponent) = if cool_name == name {
+            (
+                OwnPlayer {
                     name: cool_name,
                     pos: cool_pos.expect("!cool_pos"),
                     score: cool_score,
                     side: Side::Cold,
                 },
-                opponent: Player {
+                Player {
                     name: hot_name,
-                    #[cfg(feature = "fog_of_war")]
-                    pos: None,
-                    #[cfg(not(feature = "fog_of_war"))]
-                    pos: hot_pos.expect("!hot_pos"),
+                    pos: hot_pos,
                     score: hot_score,
                     side: Side::Hot,
                 },
-            }
+            )
         } else if hot_name == name {
-            Players {
-                #[cfg(feature = "fog_of_war")]
-                us: OwnPlayer {
-                    name: hot_name,
-                    pos: hot_pos.expect("!hot_pos"),
-                    score: hot_score,
-                    side: Side::Hot,
-                },
-                #[cfg(not(feature = "fog_of_war"))]
-                us: Player {
+            (
+                OwnPlayer {
                     name: hot_name,
                     pos: hot_pos.expect("!hot_pos"),
                     score: hot_score,
                     side: Side::Hot,
                 },
-                opponent: Player {
+                Player {
                     name: cool_name,
-                    #[cfg(feature = "fog_of_war")]
-                    pos: None,
-                    #[cfg(not(feature = "fog_of_war"))]
-                    pos: cool_pos.expect("!cool_pos"),
+                    pos: cool_pos,
                     score: cool_score,
                     side: Side::Cold,
                 },
-            }
+            )
         } else {
             unreachable!()
         };
+        let fog_of_war = match fog_of_war {
+            FogOfWar::Auto => opponent.pos.is_none(),
+            FogOfWar::Enabled => true,
+            FogOfWar::Disabled => false,
+        };
+        println!(
+            "Playing with {}",
+            if fog_of_war {
+                "fog of war"
+            } else {
+                "full information"
+            }
+        );
+        let state = GameState::new(
+            map.clone(),
+            (x_size, y_size),
+            &map_data,
+            Players { us, opponent },
+            turn,
+            fog_of_war,
+        );
+        let mut validator = (!fog_of_war && std::env::var("VALIDATE").is_ok_and(|v| !v.is_empty()))
+            .then(|| Validator::new(&state));
+        let shared = Arc::new(SharedState::new(state.clone()));
+        let state2 = Arc::clone(&shared);
+        let state3 = Arc::clone(&shared);
 
-        let state = Arc::new(Mutex::new(GameState {
-            room: map.clone(),
-            #[cfg(feature = "fog_of_war")]
-            map: Map::empty((x_size, y_size)),
-            #[cfg(not(feature = "fog_of_war"))]
-            map: map_data.clone(),
-            map_size: (x_size, y_size),
-            turns_left: turn,
-            phase: GamePhase::Starting,
-            effect: None,
-            players,
-        }));
-        let state2 = Arc::clone(&state);
-        let state3 = Arc::clone(&state);
-
-        let game = ChaserGame { client, state };
+        let game = ChaserGame {
+            client,
+            state,
+            shared,
+        };
 
         let c2s_arc1 = Arc::new(Mutex::new(None));
         let c2s_arc2 = Arc::clone(&c2s_arc1);
@@ -216,305 +714,57 @@ impl ChaserGame {
             game.client.send(C2SPacket::GetReady);
             let mut ready = false;
             let mut ended: Option<Side> = None;
-            let our_side = game.state.lock().players.us.side;
-            let mut last_search: Option<Direction> = None;
+            let our_side = game.state.players.us.side;
             loop {
                 thread::sleep(Duration::from_millis(10));
                 if ended.is_some() {
                     break;
                 }
                 if let Some(p) = game.client.recv() {
-                    match p {
-                        S2CPacket::GameResult { winner, info } => {
-                            game.state.lock().phase = GamePhase::Ended {
-                                winner,
-                                reason: info,
-                            }
-                        }
+                    let state = &mut game.state;
+                    match &p {
                         S2CPacket::UpdateBoard(GameData {
-                            map_data,
-                            cool_score,
-                            hot_score,
-                            turn,
-                            effect,
-                        }) => {
-                            let mut state = game.state.lock();
-
-                            if let Some(Effect { player, .. }) = effect {
-                                state.phase = GamePhase::Turn(player);
-
-                                if player != state.players.us.side {
-                                    game.client.send(C2SPacket::GetReady);
-                                    ready = false;
-                                }
-                            }
-
-                            #[cfg(not(feature = "fog_of_war"))]
-                            {
-                                let new_us = map_data.find_player(state.players.us.side);
-                                let new_opp = map_data.find_player(state.players.opponent.side);
-                                state.map = map_data;
-                                if let Some(new_us) = new_us {
-                                    state.players.us.pos = new_us;
-                                }
-                                if let Some(new_opp) = new_opp {
-                                    state.players.opponent.pos = new_opp;
-                                }
-                            }
-                            #[cfg(feature = "fog_of_war")]
-                            {
-                                let us = state.players.us.pos;
-                                let us_side = state.players.us.side;
-                                let new_us = map_data
-                                    .find_player(state.players.us.side)
-                                    .expect("player moved >2 blocks or was killed");
-                                state.players.us.pos = new_us;
-                                state.map.set(us.0, us.1, Element::Blank);
-                                state.map.set(new_us.0, new_us.1, us_side.to_elem());
-                            }
-                            state.turns_left = turn;
-                            state.effect = effect;
-                            state.players.assign_scores(cool_score, hot_score);
-                        }
-                        S2CPacket::GetReadyRec { rec_data } => {
-                            let mut state = game.state.lock();
-                            let pos = state.players.us.pos;
-                            let side = state.players.us.side;
-
-                            #[cfg(feature = "fog_of_war")]
-                            let mut opp = None;
-                            for (i, elem) in rec_data.into_iter().enumerate() {
-                                let x_offset = (i % 3) as isize - 1;
-                                let y_offset = (i / 3) as isize - 1;
-                                if let Some(x) = pos.0.checked_add_signed(x_offset)
-                                    && let Some(y) = pos.1.checked_add_signed(y_offset)
-                                {
-                                    if x_offset == 0 && y_offset == 0 {
-                                        _ = state.map.set(
-                                            x,
-                                            y,
-                                            if matches!(elem, RecElement::Opponent) {
-                                                Element::BothColdAndHot
-                                            } else {
-                                                side.to_elem()
-                                            },
-                                        );
-                                    } else {
-                                        _ = state.map.set(x, y, elem.into_elem(side));
-                                        #[cfg(feature = "fog_of_war")]
-                                        if matches!(elem, RecElement::Opponent) {
-                                            _ = opp.insert((x, y));
-                                        }
-                                    }
-                                }
-                            }
-
-                            #[cfg(feature = "fog_of_war")]
-                            if let Some(opp) = opp {
-                                if let Some((old_x, old_y)) =
-                                    state.players.opponent.pos.replace(opp)
-                                {
-                                    state.map.set(old_x, old_y, Element::Blank);
-                                }
-                            } else if let Some(old) = state.players.opponent.pos
-                                && state
-                                    .map
-                                    .around_8(pos, state.map_size)
-                                    .iter()
-                                    .any(|(_, pos)| *pos == old)
-                            {
-                                let (old_x, old_y) = state.players.opponent.pos.take().unwrap();
-                                state.map.set(old_x, old_y, Element::Blank);
-                            }
-
-                            ready = true;
-                        }
-                        S2CPacket::MoveRec { rec_data }
-                        | S2CPacket::PutRec { rec_data }
-                        | S2CPacket::LookRec { rec_data } => {
-                            let mut state = game.state.lock();
-                            let pos = state.players.us.pos;
-                            let side = state.players.us.side;
-
-                            let offset = match last_search {
-                                None => (0, 0),
-                                Some(Direction::Top) => (0, -2),
-                                Some(Direction::Bottom) => (0, 2),
-                                Some(Direction::Left) => (-2, 0),
-                                Some(Direction::Right) => (2, 0),
-                            };
-
-                            #[cfg(feature = "fog_of_war")]
-                            let mut opp = None;
-                            for (i, elem) in rec_data.into_iter().enumerate() {
-                                let x_offset = (i % 3) as isize - 1 + offset.0;
-                                let y_offset = (i / 3) as isize - 1 + offset.1;
-                                if let Some(x) = pos.0.checked_add_signed(x_offset)
-                                    && let Some(y) = pos.1.checked_add_signed(y_offset)
-                                {
-                                    if x_offset == 0 && y_offset == 0 {
-                                        _ = state.map.set(
-                                            x,
-                                            y,
-                                            if matches!(elem, RecElement::Opponent) {
-                                                Element::BothColdAndHot
-                                            } else {
-                                                side.to_elem()
-                                            },
-                                        );
-                                    } else {
-                                        _ = state.map.set(x, y, elem.into_elem(side));
-                                        #[cfg(feature = "fog_of_war")]
-                                        if matches!(elem, RecElement::Opponent) {
-                                            _ = opp.insert((x, y));
-                                        }
-                                    }
-                                }
-                            }
-
-                            #[cfg(feature = "fog_of_war")]
-                            if let Some(opp) = opp {
-                                if let Some((old_x, old_y)) =
-                                    state.players.opponent.pos.replace(opp)
-                                {
-                                    state.map.set(old_x, old_y, Element::Blank);
-                                }
-                            } else if let Some(old) = state.players.opponent.pos
-                                && state
-                                    .map
-                                    .around_8(pos, state.map_size)
-                                    .iter()
-                                    .any(|(_, pos)| *pos == old)
-                            {
-                                let (old_x, old_y) = state.players.opponent.pos.take().unwrap();
-                                state.map.set(old_x, old_y, Element::Blank);
-                            }
-
-                            _ = last_search.take();
-                        }
-                        S2CPacket::SearchRec { rec_data } => {
-                            if let Some(dir) = last_search {
-                                let mut state = game.state.lock();
-                                let pos = state.players.us.pos;
-                                let side = state.players.us.side;
-                                let map_size = state.map_size;
-
-                                let range: Vec<usize> = match dir {
-                                    Direction::Top => (pos.1.saturating_sub(9)
-                                        ..=pos.1.saturating_sub(1))
-                                        .rev()
-                                        .collect(),
-                                    Direction::Bottom => (min(pos.1 + 1, map_size.1)
-                                        ..=min(pos.1 + 9, map_size.1))
-                                        .collect(),
-                                    Direction::Left => (pos.0.saturating_sub(9)
-                                        ..=pos.0.saturating_sub(1))
-                                        .rev()
-                                        .collect(),
-                                    Direction::Right => (min(pos.0 + 1, map_size.0)
-                                        ..=min(pos.0 + 9, map_size.0))
-                                        .collect(),
-                                };
-                                let other_pos = match dir {
-                                    Direction::Top | Direction::Bottom => pos.0,
-                                    Direction::Left | Direction::Right => pos.1,
-                                };
-
-                                #[cfg(feature = "fog_of_war")]
-                                let mut opp = None;
-                                for (elem, pos) in rec_data.into_iter().zip(range) {
-                                    let x = if matches!(dir, Direction::Top | Direction::Bottom) {
-                                        other_pos
-                                    } else {
-                                        pos
-                                    };
-                                    let y = if matches!(dir, Direction::Left | Direction::Right) {
-                                        other_pos
-                                    } else {
-                                        pos
-                                    };
-                                    _ = state.map.set(x, y, elem.into_elem(side));
-                                    #[cfg(feature = "fog_of_war")]
-                                    if matches!(elem, RecElement::Opponent) {
-                                        _ = opp.insert((x, y));
-                                    }
-                                }
-
-                                #[cfg(feature = "fog_of_war")]
-                                if let Some(opp) = opp {
-                                    if let Some((old_x, old_y)) =
-                                        state.players.opponent.pos.replace(opp)
-                                    {
-                                        state.map.set(old_x, old_y, Element::Blank);
-                                    }
-                                } else if let Some(old) = state.players.opponent.pos
-                                    && state
-                                        .map
-                                        .around_8(pos, map_size)
-                                        .iter()
-                                        .any(|(_, pos)| *pos == old)
-                                {
-                                    let (old_x, old_y) = state.players.opponent.pos.take().unwrap();
-                                    state.map.set(old_x, old_y, Element::Blank);
-                                }
-                            }
-
-                            _ = last_search.take();
+                            effect: Some(Effect { player, .. }),
+                            ..
+                        }) if *player != state.players.us.side => {
+                            game.client.send(C2SPacket::GetReady);
+                            ready = false;
                         }
+                        S2CPacket::GetReadyRec { .. } => ready = true,
                         _ => (),
                     }
+                    if let Some(validator) = validator.as_mut() {
+                        for mismatch in validator.on_packet(&p).iter().filter(|m| !m.is_stale()) {
+                            eprintln!("tracker mismatch: {mismatch:?}");
+                        }
+                        state.validation = Some(validator.report.clone());
+                    }
+                    state.on_packet(p);
+                    game.shared.publish(state.clone());
                 }
                 // send any pending packet
-                if let GamePhase::Ended { winner, reason } = &game.state.lock().phase {
+                if let GamePhase::Ended { winner, reason } = &game.state.phase {
                     if our_side == *winner {
                         println!("We ({winner:?}) won! ({reason})");
                     } else {
                         println!("game over! We ({winner:?}) lost! ({reason})");
                     }
                     ended = Some(*winner);
+                    if let Some(validator) = &validator {
+                        println!("tracker validation: {}", validator.report);
+                    }
+                    if let Ok(path) = std::env::var("HISTORY")
+                        && let Err(e) = game.state.history.export(&path)
+                    {
+                        eprintln!("couldn't write the game history to {path}: {e}");
+                    }
                 } else {
                     match c2s_arc1.lock().take() {
                         Some(p) if ready => {
-                            if let C2SPacket::MovePlayer(dir) = p {
-                                let old_pos = game.state.lock().players.us.pos;
-                                game.state.lock().players.us.pos = match dir {
-                                    Direction::Top => (old_pos.0, old_pos.1.saturating_sub(1)),
-                                    Direction::Bottom => (old_pos.0, old_pos.1 + 1),
-                                    Direction::Left => (old_pos.0.saturating_sub(1), old_pos.1),
-                                    Direction::Right => (old_pos.0 + 1, old_pos.1),
-                                }
-                            }
-                            if let C2SPacket::Look(dir) | C2SPacket::Search(dir) = p {
-                                _ = last_search.insert(dir);
-                            } else {
-                                _ = last_search.take();
-                            }
-                            if let C2SPacket::PutWall(dir) = p {
-                                let pos = {
-                                    let state = game.state.lock();
-                                    let size = state.map_size;
-                                    let us = (
-                                        state.players.us.pos.0 as isize,
-                                        state.players.us.pos.1 as isize,
-                                    );
-                                    let shift = match dir {
-                                        Direction::Top => (0, -1),
-                                        Direction::Bottom => (0, 1),
-                                        Direction::Left => (-1, 0),
-                                        Direction::Right => (1, 0),
-                                    };
-                                    let new_wall = (us.0 + shift.0, us.1 + shift.1);
-                                    if (0..size.0 as isize).contains(&new_wall.0)
-                                        && (0..size.1 as isize).contains(&new_wall.1)
-                                    {
-                                        Some((new_wall.0 as usize, new_wall.1 as usize))
-                                    } else {
-                                        None
-                                    }
-                                };
-                                if let Some((x, y)) = pos {
-                                    game.state.lock().map.set(x, y, Element::Wall);
-                                }
+                            game.state.on_sent(&p);
+                            game.shared.publish(game.state.clone());
+                            if let Some(validator) = validator.as_mut() {
+                                validator.on_sent(&p);
                             }
                             game.client.send(p);
                             ready = false;
@@ -545,10 +795,218 @@ impl ChaserGame {
     }
 }
 impl ChaserHandle {
-    pub fn info(&self) -> MutexGuard<'_, GameState> {
-        self.state.lock()
+    /// The state as of the last packet. Cheap, and holding on to it doesn't hold up the game.
+    pub fn info(&self) -> Arc<GameState> {
+        self.state.snapshot()
+    }
+    /// What changed on the board with the latest `updata_board`.
+    pub fn board_events(&self) -> Vec<BoardEvent> {
+        self.info().board_events.clone()
+    }
+    /// Receives a new version number every time the state changes.
+    pub fn changes(&self) -> Receiver<u64> {
+        self.state.subscribe()
     }
     pub fn send(&self, packet: C2SPacket) {
         _ = self.send.lock().insert(packet);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> GameState {
+        let map: Map = "#.*..\n.C#.H\n....#".parse().unwrap();
+        let players = Players {
+            us: OwnPlayer {
+                name: "crystal".into(),
+                pos: Pos::new(1, 1),
+                score: 3,
+                side: Side::Cold,
+            },
+            opponent: Player {
+                name: "other".into(),
+                pos: Some(Pos::new(4, 1)),
+                score: 0,
+                side: Side::Hot,
+            },
+        };
+        GameState::new("room".into(), map.size(), &map, players, 40, false)
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let mut state = state();
+        state.phase = GamePhase::Ended {
+            winner: Side::Cold,
+            reason: "caught".into(),
+        };
+        state.pending = Some(Prediction::Wall {
+            pos: Pos::new(0, 2),
+        });
+        let json = serde_json::to_string(&state).unwrap();
+        let back: GameState = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.map, state.map);
+        assert_eq!(back.players, state.players);
+        assert_eq!(back.phase, state.phase);
+        assert_eq!(serde_json::to_string(&back).unwrap(), json);
+    }
+
+    #[test]
+    fn renders_as_text() {
+        let state = state();
+        assert_eq!(
+            format!("{state:#}"),
+            "room: starting, 40 turns left\n\
+             crystal (cold) at (1, 1), 3 points\n\
+             other (hot) at (4, 1), 0 points\n\
+             #.*..\n\
+             .C#.H\n\
+             ....#"
+        );
+    }
+
+    /// Under fog of war, knowing `map` already.
+    fn fog(map: &str) -> GameState {
+        let mut state = state();
+        let board = state.map.clone();
+        state = GameState::new("room".into(), board.size(), &board, state.players, 40, true);
+        state.map = map.parse().unwrap();
+        state
+    }
+    fn update(state: &mut GameState, map_data: &str, hot_score: u32, effect: Option<Effect>) {
+        state.on_packet(S2CPacket::UpdateBoard(GameData {
+            map_data: map_data.parse().unwrap(),
+            cool_score: 3,
+            hot_score,
+            turn: state.turns_left - 1,
+            effect,
+        }));
+    }
+    const OURS: Option<Effect> = Some(Effect {
+        search: SearchType::AroundCurrent,
+        player: Side::Cold,
+        direction: None,
+    });
+    const THEIRS: Option<Effect> = Some(Effect {
+        search: SearchType::AroundCurrent,
+        player: Side::Hot,
+        direction: None,
+    });
+
+    #[test]
+    fn blocked_move_finds_a_wall() {
+        let mut state = fog("?????\n?C???\n?????");
+        state.on_sent(&C2SPacket::MovePlayer(Direction::Right));
+        assert_eq!(state.players.us.pos, Pos::new(2, 1));
+        // the server left us where we were
+        update(&mut state, "#.*..\n.C#.H\n....#", 0, OURS);
+        assert_eq!(state.players.us.pos, Pos::new(1, 1));
+        assert_eq!(state.pending, None);
+        assert_eq!(
+            state.desyncs,
+            [Desync::Move {
+                predicted: Pos::new(2, 1),
+                actual: Pos::new(1, 1),
+            }]
+        );
+        assert_eq!(state.map.at(Pos::new(2, 1)), Element::Wall);
+        assert_eq!(
+            state.changes,
+            [MapChange {
+                pos: Pos::new(2, 1),
+                from: Element::Unknown,
+                to: Element::Wall,
+                cause: ChangeCause::BlockedMove,
+            }]
+        );
+    }
+
+    #[test]
+    fn rejected_wall_is_undone() {
+        let mut state = state();
+        state.on_sent(&C2SPacket::PutWall(Direction::Bottom));
+        assert_eq!(state.map.at(Pos::new(1, 2)), Element::Wall);
+        update(&mut state, "#.*..\n.C#.H\n....#", 0, OURS);
+        assert_eq!(state.map.at(Pos::new(1, 2)), Element::Blank);
+        assert_eq!(
+            state.desyncs,
+            [Desync::Wall {
+                pos: Pos::new(1, 2),
+                actual: Element::Blank,
+            }]
+        );
+        assert_eq!(state.pending, None);
+    }
+
+    #[test]
+    fn heart_pickup_places_the_opponent() {
+        let mut state = fog("#.*..\n.C#..\n....#");
+        state.opponent_belief = OpponentBelief::certain(Pos::new(3, 0), state.map_size);
+        update(&mut state, "#.H..\n.C#..\n....#", 1, THEIRS);
+        assert_eq!(state.inferred, [OpponentAction::PickedHeart { gained: 1 }]);
+        // (2, 0) is the only heart they could have reached
+        assert_eq!(state.players.opponent.pos, Some(Pos::new(2, 0)));
+        assert_eq!(state.map.at(Pos::new(2, 0)), Element::Hot);
+        assert_eq!(state.opponent_belief.at(Pos::new(2, 0)), 1.0);
+    }
+
+    #[test]
+    fn heart_pickup_between_several_hearts() {
+        let mut state = fog("#.*.*\n.C#..\n....#");
+        state.opponent_belief = OpponentBelief::certain(Pos::new(3, 0), state.map_size);
+        update(&mut state, "#...H\n.C#..\n....#", 1, THEIRS);
+        assert_eq!(state.players.opponent.pos, None);
+        assert_eq!(state.opponent_belief.at(Pos::new(2, 0)), 0.5);
+        assert_eq!(state.opponent_belief.at(Pos::new(4, 0)), 0.5);
+        assert_eq!(state.opponent_belief.at(Pos::new(3, 0)), 0.0);
+        assert_eq!(state.map.at(Pos::new(2, 0)), Element::Heart);
+    }
+
+    #[test]
+    fn heart_pickup_next_to_an_unknown_cell() {
+        // they could also have found a heart at (4, 0) we haven't seen
+        let mut state = fog("#.*.?\n.C#..\n....#");
+        state.opponent_belief = OpponentBelief::certain(Pos::new(3, 0), state.map_size);
+        update(&mut state, "#...H\n.C#..\n....#", 1, THEIRS);
+        assert_eq!(state.players.opponent.pos, None);
+        assert_eq!(state.opponent_belief.at(Pos::new(2, 0)), 0.5);
+        assert_eq!(state.opponent_belief.at(Pos::new(4, 0)), 0.5);
+        assert_eq!(state.map.at(Pos::new(2, 0)), Element::Heart);
+    }
+
+    #[test]
+    fn scan_that_misses_the_opponent() {
+        let mut state = fog("#.*.*\n.C#..\n....#");
+        state.opponent_belief = OpponentBelief::certain(Pos::new(3, 0), state.map_size);
+        state.opponent_belief.propagate(&state.map);
+        state.on_sent(&C2SPacket::Search(Direction::Right));
+        let mut rec_data = vec![RecElement::Blank; 9];
+        rec_data[0] = RecElement::Wall;
+        state.on_packet(S2CPacket::SearchRec { rec_data });
+        assert_eq!(state.players.opponent.pos, None);
+        assert_eq!(state.opponent_belief.at(Pos::new(3, 1)), 0.0);
+        for pos in [Pos::new(3, 0), Pos::new(2, 0), Pos::new(4, 0)] {
+            assert!((state.opponent_belief.at(pos) - 1.0 / 3.0).abs() < 1e-9);
+        }
+        assert!(state.last_seen.at(Pos::new(4, 1)).is_some());
+        assert!(state.changes.is_empty());
+    }
+
+    #[test]
+    fn move_scan_waits_for_the_board() {
+        let mut state = fog("?????\n?C???\n?????");
+        state.on_sent(&C2SPacket::MovePlayer(Direction::Left));
+        let mut rec_data = vec![RecElement::Blank; 9];
+        rec_data[1] = RecElement::Wall;
+        state.on_packet(S2CPacket::MoveRec { rec_data });
+        assert_eq!(state.map.at(Pos::new(0, 0)), Element::Unknown);
+        update(&mut state, "#.*..\nC.#.H\n....#", 0, OURS);
+        // read from where we ended up, not from where we were
+        assert_eq!(state.players.us.pos, Pos::new(0, 1));
+        assert_eq!(state.map.at(Pos::new(0, 0)), Element::Wall);
+        assert_eq!(state.map.at(Pos::new(1, 0)), Element::Blank);
+        assert!(state.desyncs.is_empty());
+    }
+}
@@ -1,7 +1,9 @@
-use std::{cmp::min, fmt::Display};
+use std::{fmt::Display, ops::Deref, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 
+use crate::grid::Grid;
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(into = "String")]
 #[serde(from = "String")]
@@ -34,6 +36,23 @@ impl From<String> for Direction {
     }
 }
 impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Top,
+        Direction::Bottom,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    /// The direction of a single orthogonal step, `None` for anything else.
+    pub const fn from_delta(dx: isize, dy: isize) -> Option<Direction> {
+        match (dx, dy) {
+            (0, -1) => Some(Direction::Top),
+            (0, 1) => Some(Direction::Bottom),
+            (-1, 0) => Some(Direction::Left),
+            (1, 0) => Some(Direction::Right),
+            _ => None,
+        }
+    }
     pub const fn flip(self) -> Self {
         match self {
             Direction::Top => Direction::Bottom,
@@ -50,6 +69,15 @@ impl Direction {
             Direction::Right => Direction::Top,
         }
     }
+    /// Unit step in board coordinates, with y growing towards the bottom.
+    pub const fn delta(self) -> (isize, isize) {
+        match self {
+            Direction::Top => (0, -1),
+            Direction::Bottom => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
     pub const fn right(self) -> Self {
         match self {
             Direction::Top => Direction::Right,
@@ -60,9 +88,90 @@ impl Direction {
     }
 }
 
+/// A cell on the board, with `y` growing towards the bottom.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+pub struct Pos {
+    pub x: usize,
+    pub y: usize,
+}
+impl Pos {
+    pub const fn new(x: usize, y: usize) -> Pos {
+        Pos { x, y }
+    }
+
+    /// The neighbouring cell in `dir`, `None` if that would leave the top or left edge.
+    pub fn step(self, dir: Direction) -> Option<Pos> {
+        let (dx, dy) = dir.delta();
+        self.offset(dx, dy)
+    }
+    /// Like [`Pos::step`], but also stays within a board of `size`.
+    pub fn checked_step(self, dir: Direction, size: (usize, usize)) -> Option<Pos> {
+        self.step(dir).filter(|p| p.in_bounds(size))
+    }
+    pub fn offset(self, dx: isize, dy: isize) -> Option<Pos> {
+        Some(Pos {
+            x: self.x.checked_add_signed(dx)?,
+            y: self.y.checked_add_signed(dy)?,
+        })
+    }
+    pub fn checked_offset(self, dx: isize, dy: isize, size: (usize, usize)) -> Option<Pos> {
+        self.offset(dx, dy).filter(|p| p.in_bounds(size))
+    }
+    pub const fn in_bounds(self, size: (usize, usize)) -> bool {
+        self.x < size.0 && self.y < size.1
+    }
+
+    pub const fn manhattan(self, other: Pos) -> usize {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+    /// The direction to take from `self` to reach the adjacent cell `other`.
+    pub fn direction_to(self, other: Pos) -> Option<Direction> {
+        Direction::from_delta(
+            other.x as isize - self.x as isize,
+            other.y as isize - self.y as isize,
+        )
+    }
+
+    /// Orthogonal neighbours on a board of `size`.
+    pub fn neighbors(self, size: (usize, usize)) -> impl Iterator<Item = (Pos, Direction)> {
+        [
+            Direction::Left,
+            Direction::Right,
+            Direction::Top,
+            Direction::Bottom,
+        ]
+        .into_iter()
+        .filter_map(move |dir| Some((self.checked_step(dir, size)?, dir)))
+    }
+    /// The up to 3x3 block centred on `self`, including `self`.
+    pub fn around_8(self, size: (usize, usize)) -> impl Iterator<Item = Pos> {
+        (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter_map(move |(dx, dy)| self.checked_offset(dx, dy, size))
+    }
+}
+impl From<(usize, usize)> for Pos {
+    fn from((x, y): (usize, usize)) -> Self {
+        Pos { x, y }
+    }
+}
+impl From<Pos> for (usize, usize) {
+    fn from(pos: Pos) -> Self {
+        (pos.x, pos.y)
+    }
+}
+impl Display for Pos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
 #[repr(u8)]
-#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(from = "u8")]
+#[serde(into = "u8")]
 pub enum Element {
     Blank,
     Wall,
@@ -70,6 +179,8 @@ pub enum Element {
     Cold,
     Hot,
     BothColdAndHot,
+    /// Never sent by the server, marks cells we haven't observed yet under fog of war.
+    Unknown,
 }
 impl From<u8> for Element {
     fn from(value: u8) -> Self {
@@ -80,11 +191,60 @@ impl From<u8> for Element {
             3 => Element::Cold,
             4 => Element::Hot,
             34 | 43 => Element::BothColdAndHot,
+            UNKNOWN => Element::Unknown,
             n => panic!("unwhown Element {n}"),
         }
     }
 }
+/// Not part of the protocol, only used when we save a fog-of-war map ourselves.
+const UNKNOWN: u8 = 255;
+impl From<Element> for u8 {
+    fn from(value: Element) -> Self {
+        match value {
+            Element::Blank => 0,
+            Element::Wall => 1,
+            Element::Heart => 2,
+            Element::Cold => 3,
+            Element::Hot => 4,
+            Element::BothColdAndHot => 34,
+            Element::Unknown => UNKNOWN,
+        }
+    }
+}
 impl Element {
+    pub const ALL: [Element; 7] = [
+        Element::Blank,
+        Element::Wall,
+        Element::Heart,
+        Element::Cold,
+        Element::Hot,
+        Element::BothColdAndHot,
+        Element::Unknown,
+    ];
+    /// Single character stand-in for the [`Display`] glyph, for terminals and files that
+    /// can't take emoji. `{:#}` prints this too.
+    pub const fn ascii(self) -> char {
+        match self {
+            Element::Blank => '.',
+            Element::Wall => '#',
+            Element::Heart => '*',
+            Element::Cold => 'C',
+            Element::Hot => 'H',
+            Element::BothColdAndHot => 'X',
+            Element::Unknown => '?',
+        }
+    }
+    pub const fn glyph(self) -> &'static str {
+        match self {
+            Element::Blank => " ",
+            Element::Wall => "⬛",
+            Element::Heart => "❤",
+            Element::Cold => "❄",
+            Element::Hot => "🔥",
+            Element::BothColdAndHot => "❄🔥",
+            Element::Unknown => "?",
+        }
+    }
     pub fn to_side(self) -> Side {
         match self {
             Element::Hot => Side::Hot,
@@ -100,24 +260,28 @@ impl Side {
             Self::Cold => Element::Cold,
         }
     }
+    pub fn other(self) -> Side {
+        match self {
+            Self::Hot => Self::Cold,
+            Self::Cold => Self::Hot,
+        }
+    }
 }
 impl Display for Element {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
-            Element::Blank => " ",
-            Element::Wall => "⬛",
-            Element::Heart => "❤",
-            Element::Cold => "❄",
-            Element::Hot => "🔥",
-            Element::BothColdAndHot => "❄🔥",
-        })
+        if f.alternate() {
+            write!(f, "{}", self.ascii())
+        } else {
+            f.write_str(self.glyph())
+        }
     }
 }
 
 /// WHY NOT USE THE SAME ONE??
 #[repr(u8)]
-#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(from = "u8")]
+#[serde(into = "u8")]
 pub enum RecElement {
     Blank,
     Opponent,
@@ -135,6 +299,11 @@ impl From<u8> for RecElement {
         }
     }
 }
+impl From<RecElement> for u8 {
+    fn from(value: RecElement) -> Self {
+        value as u8
+    }
+}
 impl RecElement {
     pub fn into_elem(self, our_side: Side) -> Element {
         match self {
@@ -149,7 +318,7 @@ impl RecElement {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct GameData {
     pub map_data: Map,
     pub cool_score: u32,
@@ -159,7 +328,7 @@ pub struct GameData {
     pub effect: Option<Effect>,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[allow(dead_code)]
 pub struct Effect {
     #[serde(rename = "t")]
@@ -171,134 +340,112 @@ pub struct Effect {
 }
 
 #[repr(transparent)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(transparent)]
-pub struct Map(Vec<Vec<Element>>);
+pub struct Map(Grid<Element>);
 impl Map {
     pub fn empty(size: (usize, usize)) -> Map {
-        Map(vec![vec![Element::Blank; size.0]; size.1])
+        Map(Grid::new(size, Element::Blank))
+    }
+    pub fn unknown(size: (usize, usize)) -> Map {
+        Map(Grid::new(size, Element::Unknown))
     }
 
-    pub fn at(&self, x: usize, y: usize) -> Element {
-        self.0[y][x]
+    /// Panics if `pos` is off the board, use [`Map::get`] when it might be.
+    pub fn at(&self, pos: Pos) -> Element {
+        self.0[pos]
     }
-    pub fn set(&mut self, x: usize, y: usize, elem: Element) -> bool {
-        let e = self.0.get_mut(y).and_then(|row| row.get_mut(x));
-        if let Some(e) = e {
-            *e = elem;
-            true
-        } else {
-            false
-        }
+    pub fn get(&self, pos: Pos) -> Option<Element> {
+        self.0.get(pos).copied()
     }
-    pub fn find_player(&self, side: Side) -> Option<(usize, usize)> {
-        let to_find = match side {
-            Side::Hot => Element::Hot,
-            Side::Cold => Element::Cold,
-        };
-        for (i, row) in self.0.iter().enumerate() {
-            for (j, &val) in row.iter().enumerate() {
-                if val == to_find || val == Element::BothColdAndHot {
-                    return Some((j, i));
-                }
-            }
-        }
-        None
-    }
-    pub fn find_player_around(
-        &self,
-        side: Side,
-        old_pos: (usize, usize),
-        size: (usize, usize),
-    ) -> Option<(usize, usize)> {
-        let to_find = match side {
-            Side::Hot => Element::Hot,
-            Side::Cold => Element::Cold,
-        };
-        let min_x = old_pos.0.saturating_sub(1);
-        let max_x = min(old_pos.0 + 1, size.0 - 1);
-        let min_y = old_pos.1.saturating_sub(1);
-        let max_y = min(old_pos.1 + 1, size.1 - 1);
-
-        for i in min_x..=max_x {
-            for j in min_y..=max_y {
-                let at = self.at(i, j);
-                if at == to_find || at == Element::BothColdAndHot {
-                    return Some((i, j));
-                }
-            }
-        }
-        None
-    }
-
-    pub fn around_8(
-        &self,
-        pos: (usize, usize),
-        size: (usize, usize),
-    ) -> Vec<(Element, (usize, usize))> {
-        let (pos_x, pos_y) = (pos.0 as isize, pos.1 as isize);
-        let mut res = vec![];
-        for o_x in -1..=1 {
-            for o_y in -1..=1 {
-                let np_x = pos_x + o_x;
-                let np_y = pos_y + o_y;
-                if (0..size.0 as isize).contains(&np_x) && (0..size.1 as isize).contains(&np_y) {
-                    let (np_x, np_y) = (np_x as usize, np_y as usize);
-                    res.push((self.at(np_x, np_y), (np_x, np_y)));
-                }
-            }
-        }
-        res
-    }
-    pub fn around_4(
-        &self,
-        pos: (usize, usize),
-        size: (usize, usize),
-    ) -> Vec<(Element, (usize, usize), Direction)> {
-        let (pos_x, pos_y) = (pos.0 as isize, pos.1 as isize);
-        let mut surrounding = vec![];
-        let potentials = [
-            (-1, 0, Direction::Left),
-            (1, 0, Direction::Right),
-            (0, -1, Direction::Top),
-            (0, 1, Direction::Bottom),
-        ];
-        for (o_x, o_y, dir) in potentials {
-            let (np_x, np_y) = (pos_x + o_x, pos_y + o_y);
-            if (0..size.0 as isize).contains(&np_x) && (0..size.1 as isize).contains(&np_y) {
-                let (np_x, np_y) = (np_x as usize, np_y as usize);
-                surrounding.push((self.at(np_x, np_y), (np_x, np_y), dir));
-            }
-        }
-        surrounding
+    pub fn set(&mut self, pos: Pos, elem: Element) -> bool {
+        self.0.set(pos, elem)
+    }
+    pub fn find_player(&self, side: Side) -> Option<Pos> {
+        let to_find = side.to_elem();
+        self.0
+            .iter()
+            .find(|&(_, &val)| val == to_find || val == Element::BothColdAndHot)
+            .map(|(pos, _)| pos)
+    }
+    pub fn find_player_around(&self, side: Side, old_pos: Pos) -> Option<Pos> {
+        let to_find = side.to_elem();
+        self.0
+            .around_8(old_pos)
+            .find(|&(_, &at)| at == to_find || at == Element::BothColdAndHot)
+            .map(|(pos, _)| pos)
     }
 
-    pub fn hearts_near(&self, pos: (usize, usize)) -> Vec<(usize, usize)> {
-        let mut hearts = vec![];
+    pub fn around_8(&self, pos: Pos) -> Vec<(Element, Pos)> {
+        self.0.around_8(pos).map(|(pos, &e)| (e, pos)).collect()
+    }
+    pub fn around_4(&self, pos: Pos) -> Vec<(Element, Pos, Direction)> {
+        self.0
+            .neighbors(pos)
+            .map(|(pos, dir, &e)| (e, pos, dir))
+            .collect()
+    }
 
-        for (i, row) in self.0.iter().enumerate() {
-            for (j, &val) in row.iter().enumerate() {
-                if val == Element::Heart {
-                    hearts.push((j, i));
-                }
-            }
-        }
-        hearts.sort_by(|&a, &b| {
-            let a_dst = pos.0.abs_diff(a.0) + pos.1.abs_diff(a.1);
-            let b_dst = pos.0.abs_diff(b.0) + pos.1.abs_diff(b.1);
-            a_dst.cmp(&b_dst)
-        });
+    pub fn hearts_near(&self, pos: Pos) -> Vec<Pos> {
+        let mut hearts: Vec<_> = self
+            .0
+            .iter()
+            .filter(|&(_, &val)| val == Element::Heart)
+            .map(|(pos, _)| pos)
+            .collect();
+        hearts.sort_by_key(|&heart| pos.manhattan(heart));
         hearts
     }
     pub fn deadlocked(&self) -> bool {
-        for row in self.0.iter() {
-            for &val in row.iter() {
-                if val == Element::BothColdAndHot {
-                    return true;
+        self.0.values().any(|&val| val == Element::BothColdAndHot)
+    }
+}
+impl Deref for Map {
+    type Target = Grid<Element>;
+    fn deref(&self) -> &Grid<Element> {
+        &self.0
+    }
+}
+/// One line per row using the [`Element`] glyphs, or [`Element::ascii`] with `{:#}`.
+impl Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (y, row) in self.0.rows().enumerate() {
+            if y > 0 {
+                writeln!(f)?;
+            }
+            for elem in row {
+                elem.fmt(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+/// Reads back either form of [`Display`], one line per row.
+impl FromStr for Map {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // longest glyph first so "❄🔥" isn't read as "❄" followed by "🔥"
+        let mut glyphs: Vec<_> = Element::ALL.iter().map(|&e| (e.glyph(), e)).collect();
+        glyphs.sort_by_key(|(glyph, _)| std::cmp::Reverse(glyph.len()));
+
+        let mut rows = vec![];
+        for (y, mut line) in s.lines().enumerate() {
+            let mut row = vec![];
+            while let Some(c) = line.chars().next() {
+                if let Some(&e) = Element::ALL.iter().find(|e| e.ascii() == c) {
+                    row.push(e);
+                    line = &line[c.len_utf8()..];
+                } else if let Some(&(glyph, e)) = glyphs.iter().find(|(g, _)| line.starts_with(g)) {
+                    row.push(e);
+                    line = &line[glyph.len()..];
+                } else {
+                    return Err(format!("unknown cell {c:?} on line {}", y + 1));
                 }
             }
+            rows.push(row);
         }
-        false
+        Grid::try_from(rows).map(Map).map_err(|e| e.to_string())
     }
 }
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -321,14 +468,15 @@ impl From<String> for Side {
     fn from(value: String) -> Self {
         match value.as_str() {
             "hot" => Side::Hot,
-            "cool" => Side::Cold,
+            // the server calls it "cool", we write "cold"
+            "cool" | "cold" => Side::Cold,
             "draw" => panic!("draw"),
             e => panic!("unknown side {e}"),
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(into = "String")]
 #[serde(from = "String")]
 pub enum SearchType {
@@ -356,3 +504,304 @@ impl From<String> for SearchType {
         }
     }
 }
+
+/// Footprint of one of our scans. Each scan answers with `rec_data`, and [`ScanShape::cells`]
+/// says which board cell every entry of it describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScanShape {
+    /// The 3x3 around us sent back by `get_ready`, `move` and `put`, row by row.
+    Around,
+    /// A 3x3 centred two cells away in the given direction, row by row.
+    Look(Direction),
+    /// The 9 cells in a line starting next to us, nearest first.
+    Search(Direction),
+}
+impl ScanShape {
+    /// The shape of a scan announced in an [`Effect`].
+    pub fn from_effect(effect: &Effect) -> Option<ScanShape> {
+        match (effect.search, effect.direction) {
+            (SearchType::AroundCurrent, _) => Some(ScanShape::Around),
+            (SearchType::AroundSide, Some(dir)) => Some(ScanShape::Look(dir)),
+            (SearchType::Direction, Some(dir)) => Some(ScanShape::Search(dir)),
+            _ => None,
+        }
+    }
+    pub fn search_type(self) -> SearchType {
+        match self {
+            ScanShape::Around => SearchType::AroundCurrent,
+            ScanShape::Look(_) => SearchType::AroundSide,
+            ScanShape::Search(_) => SearchType::Direction,
+        }
+    }
+
+    /// Offsets from the scanning player, in `rec_data` order.
+    pub fn offsets(self) -> [(isize, isize); 9] {
+        let centre = match self {
+            ScanShape::Around => (0, 0),
+            ScanShape::Look(dir) => {
+                let (x, y) = dir.delta();
+                (x * 2, y * 2)
+            }
+            ScanShape::Search(dir) => {
+                let (x, y) = dir.delta();
+                return std::array::from_fn(|i| (x * (i as isize + 1), y * (i as isize + 1)));
+            }
+        };
+        std::array::from_fn(|i| {
+            (
+                centre.0 + (i % 3) as isize - 1,
+                centre.1 + (i / 3) as isize - 1,
+            )
+        })
+    }
+    /// Absolute cells scanned from `pos`, in `rec_data` order. Entries that fall off the board
+    /// are `None` so indices still line up with `rec_data`.
+    pub fn cells(self, pos: Pos, map_size: (usize, usize)) -> [Option<Pos>; 9] {
+        self.offsets()
+            .map(|(o_x, o_y)| pos.checked_offset(o_x, o_y, map_size))
+    }
+    /// Whether a scan from `pos` covers `cell`.
+    pub fn covers(self, pos: Pos, map_size: (usize, usize), cell: Pos) -> bool {
+        self.cells(pos, map_size).contains(&Some(cell))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (usize, usize) = (15, 17);
+
+    fn shapes() -> Vec<ScanShape> {
+        let mut shapes = vec![ScanShape::Around];
+        shapes.extend(Direction::ALL.map(ScanShape::Look));
+        shapes.extend(Direction::ALL.map(ScanShape::Search));
+        shapes
+    }
+
+    #[test]
+    fn cells_match_offsets_everywhere() {
+        for shape in shapes() {
+            for y in 0..SIZE.1 {
+                for x in 0..SIZE.0 {
+                    let cells = shape.cells(Pos::new(x, y), SIZE);
+                    for (cell, (o_x, o_y)) in cells.iter().zip(shape.offsets()) {
+                        let (t_x, t_y) = (x as isize + o_x, y as isize + o_y);
+                        let on_board = (0..SIZE.0 as isize).contains(&t_x)
+                            && (0..SIZE.1 as isize).contains(&t_y);
+                        let expected = on_board.then_some(Pos::new(t_x as usize, t_y as usize));
+                        assert_eq!(*cell, expected, "{shape:?} from ({x}, {y})");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn around_in_corners() {
+        let cells = ScanShape::Around.cells(Pos::new(0, 0), SIZE);
+        assert_eq!(
+            cells,
+            [
+                None,
+                None,
+                None,
+                None,
+                Some(Pos::new(0, 0)),
+                Some(Pos::new(1, 0)),
+                None,
+                Some(Pos::new(0, 1)),
+                Some(Pos::new(1, 1)),
+            ]
+        );
+        let (max_x, max_y) = (SIZE.0 - 1, SIZE.1 - 1);
+        let cells = ScanShape::Around.cells(Pos::new(max_x, max_y), SIZE);
+        assert_eq!(cells[0], Some(Pos::new(max_x - 1, max_y - 1)));
+        assert_eq!(cells[4], Some(Pos::new(max_x, max_y)));
+        assert_eq!(cells[2], None);
+        assert_eq!(cells[6], None);
+        assert_eq!(cells[8], None);
+    }
+
+    #[test]
+    fn look_is_shifted_two_cells() {
+        let cells = ScanShape::Look(Direction::Top).cells(Pos::new(5, 5), SIZE);
+        assert_eq!(cells[0], Some(Pos::new(4, 2)));
+        assert_eq!(cells[4], Some(Pos::new(5, 3)));
+        assert_eq!(cells[8], Some(Pos::new(6, 4)));
+
+        // only the near row of a look past the edge stays on the board
+        let cells = ScanShape::Look(Direction::Top).cells(Pos::new(5, 1), SIZE);
+        assert!(cells[..6].iter().all(Option::is_none));
+        assert_eq!(
+            cells[6..],
+            [
+                Some(Pos::new(4, 0)),
+                Some(Pos::new(5, 0)),
+                Some(Pos::new(6, 0))
+            ]
+        );
+
+        let cells = ScanShape::Look(Direction::Right).cells(Pos::new(SIZE.0 - 1, 5), SIZE);
+        assert!(cells.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn search_is_nearest_first_and_clipped() {
+        let cells = ScanShape::Search(Direction::Left).cells(Pos::new(10, 3), SIZE);
+        assert_eq!(cells[0], Some(Pos::new(9, 3)));
+        assert_eq!(cells[8], Some(Pos::new(1, 3)));
+
+        let cells = ScanShape::Search(Direction::Top).cells(Pos::new(4, 2), SIZE);
+        assert_eq!(
+            cells[..3],
+            [Some(Pos::new(4, 1)), Some(Pos::new(4, 0)), None]
+        );
+        assert!(cells[2..].iter().all(Option::is_none));
+
+        // searching off the edge never lands on our own cell
+        for dir in Direction::ALL {
+            for pos in [Pos::new(0, 0), Pos::new(SIZE.0 - 1, SIZE.1 - 1)] {
+                let cells = ScanShape::Search(dir).cells(pos, SIZE);
+                assert!(!cells.contains(&Some(pos)), "{dir:?} from {pos}");
+            }
+        }
+
+        let cells = ScanShape::Search(Direction::Bottom).cells(Pos::new(0, SIZE.1 - 2), SIZE);
+        assert_eq!(cells[0], Some(Pos::new(0, SIZE.1 - 1)));
+        assert!(cells[1..].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn effect_shapes() {
+        let effect = |search, direction| Effect {
+            search,
+            player: Side::Hot,
+            direction,
+        };
+        assert_eq!(
+            ScanShape::from_effect(&effect(SearchType::AroundCurrent, None)),
+            Some(ScanShape::Around)
+        );
+        assert_eq!(
+            ScanShape::from_effect(&effect(SearchType::AroundSide, Some(Direction::Left))),
+            Some(ScanShape::Look(Direction::Left))
+        );
+        assert_eq!(
+            ScanShape::from_effect(&effect(SearchType::Direction, Some(Direction::Bottom))),
+            Some(ScanShape::Search(Direction::Bottom))
+        );
+        assert_eq!(
+            ScanShape::from_effect(&effect(SearchType::Direction, None)),
+            None
+        );
+    }
+
+    #[test]
+    fn steps_stop_at_every_edge() {
+        let (max_x, max_y) = (SIZE.0 - 1, SIZE.1 - 1);
+        let origin = Pos::new(0, 0);
+        assert_eq!(origin.step(Direction::Left), None);
+        assert_eq!(origin.step(Direction::Top), None);
+        assert_eq!(origin.step(Direction::Right), Some(Pos::new(1, 0)));
+        assert_eq!(origin.step(Direction::Bottom), Some(Pos::new(0, 1)));
+
+        // `step` knows no board size, `checked_step` does
+        let corner = Pos::new(max_x, max_y);
+        assert_eq!(corner.step(Direction::Right), Some(Pos::new(SIZE.0, max_y)));
+        assert_eq!(corner.checked_step(Direction::Right, SIZE), None);
+        assert_eq!(corner.checked_step(Direction::Bottom, SIZE), None);
+        assert_eq!(
+            corner.checked_step(Direction::Left, SIZE),
+            Some(Pos::new(max_x - 1, max_y))
+        );
+        assert_eq!(
+            corner.checked_step(Direction::Top, SIZE),
+            Some(Pos::new(max_x, max_y - 1))
+        );
+        assert_eq!(Pos::new(0, max_y).checked_step(Direction::Left, SIZE), None);
+        assert_eq!(Pos::new(max_x, 0).checked_step(Direction::Top, SIZE), None);
+
+        assert_eq!(origin.checked_offset(-1, 0, SIZE), None);
+        assert_eq!(origin.checked_offset(0, -1, SIZE), None);
+        assert_eq!(corner.checked_offset(1, 0, SIZE), None);
+        assert_eq!(corner.checked_offset(0, 1, SIZE), None);
+        assert_eq!(
+            corner.checked_offset(-2, -3, SIZE),
+            Some(Pos::new(max_x - 2, max_y - 3))
+        );
+        assert_eq!(Pos::new(1, 1).checked_offset(-2, 0, SIZE), None);
+    }
+
+    #[test]
+    fn directions_between_neighbours() {
+        for dir in Direction::ALL {
+            let (dx, dy) = dir.delta();
+            assert_eq!(Direction::from_delta(dx, dy), Some(dir));
+            let pos = Pos::new(5, 5);
+            let to = pos.step(dir).unwrap();
+            assert_eq!(pos.direction_to(to), Some(dir));
+            assert_eq!(to.direction_to(pos), Some(dir.flip()));
+        }
+        assert_eq!(Direction::from_delta(0, 0), None);
+        assert_eq!(Direction::from_delta(1, 1), None);
+        assert_eq!(Direction::from_delta(2, 0), None);
+        // no wrapping around to the other side of the board
+        assert_eq!(Pos::new(0, 3).direction_to(Pos::new(SIZE.0 - 1, 3)), None);
+        assert_eq!(Pos::new(3, 3).direction_to(Pos::new(3, 3)), None);
+        assert_eq!(Pos::new(0, 0).direction_to(Pos::new(1, 1)), None);
+    }
+
+    #[test]
+    fn neighbours_in_corners_and_on_edges() {
+        let (max_x, max_y) = (SIZE.0 - 1, SIZE.1 - 1);
+        let neighbours = |pos: Pos| {
+            let mut cells: Vec<Pos> = pos.neighbors(SIZE).map(|(n, _)| n).collect();
+            cells.sort();
+            cells
+        };
+        assert_eq!(neighbours(Pos::new(0, 0)), [Pos::new(0, 1), Pos::new(1, 0)]);
+        assert_eq!(
+            neighbours(Pos::new(max_x, max_y)),
+            [Pos::new(max_x - 1, max_y), Pos::new(max_x, max_y - 1)]
+        );
+        assert_eq!(neighbours(Pos::new(0, 5)).len(), 3);
+        assert_eq!(neighbours(Pos::new(max_x, 5)).len(), 3);
+        assert_eq!(neighbours(Pos::new(5, max_y)).len(), 3);
+        assert_eq!(neighbours(Pos::new(5, 5)).len(), 4);
+        for (n, dir) in Pos::new(0, max_y).neighbors(SIZE) {
+            assert_eq!(Pos::new(0, max_y).direction_to(n), Some(dir));
+        }
+        // a single cell has none
+        assert_eq!(Pos::new(0, 0).neighbors((1, 1)).count(), 0);
+    }
+
+    const FIXTURE: &str = "\
+#.*..
+.C#?X
+H...#";
+
+    #[test]
+    fn map_text_round_trips() {
+        let map: Map = FIXTURE.parse().unwrap();
+        assert_eq!(map.size(), (5, 3));
+        assert_eq!(map.at(Pos::new(1, 1)), Element::Cold);
+        assert_eq!(map.at(Pos::new(3, 1)), Element::Unknown);
+        assert_eq!(map.at(Pos::new(4, 1)), Element::BothColdAndHot);
+        assert_eq!(format!("{map:#}"), FIXTURE);
+        assert_eq!(map.to_string().parse::<Map>(), Ok(map));
+        assert!("..\n.".parse::<Map>().is_err());
+        assert!("..\n.!".parse::<Map>().is_err());
+    }
+
+    #[test]
+    fn map_json_round_trips() {
+        let map: Map = FIXTURE.parse().unwrap();
+        let json = serde_json::to_string(&map).unwrap();
+        assert!(json.starts_with("[[1,0,2,0,0],[0,3,1,255,34]"));
+        assert_eq!(serde_json::from_str::<Map>(&json).unwrap(), map);
+        let side: Side =
+            serde_json::from_str(&serde_json::to_string(&Side::Cold).unwrap()).unwrap();
+        assert_eq!(side, Side::Cold);
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    fmt::{self, Display},
+    ops::{Index, IndexMut},
+};
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::game_types::{Direction, Pos};
+
+/// Row-major board-shaped storage, one `T` per cell.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "Vec<Vec<T>>")]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(size: (usize, usize), value: T) -> Grid<T> {
+        Grid {
+            width: size.0,
+            height: size.1,
+            cells: vec![value; size.0 * size.1],
+        }
+    }
+    pub fn fill(&mut self, value: T) {
+        self.cells.fill(value);
+    }
+}
+impl<T> Grid<T> {
+    pub fn from_fn(size: (usize, usize), mut f: impl FnMut(Pos) -> T) -> Grid<T> {
+        let cells = (0..size.0 * size.1)
+            .map(|i| f(Pos::new(i % size.0, i / size.0)))
+            .collect();
+        Grid {
+            width: size.0,
+            height: size.1,
+            cells,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+    pub fn contains(&self, pos: Pos) -> bool {
+        pos.in_bounds(self.size())
+    }
+    fn index_of(&self, pos: Pos) -> Option<usize> {
+        self.contains(pos).then(|| pos.y * self.width + pos.x)
+    }
+
+    pub fn get(&self, pos: Pos) -> Option<&T> {
+        self.index_of(pos).map(|i| &self.cells[i])
+    }
+    pub fn get_mut(&mut self, pos: Pos) -> Option<&mut T> {
+        self.index_of(pos).map(|i| &mut self.cells[i])
+    }
+    /// Stores `value` at `pos`, returning false if it is off the board.
+    pub fn set(&mut self, pos: Pos, value: T) -> bool {
+        match self.get_mut(pos) {
+            Some(cell) => {
+                *cell = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Pos> + use<T> {
+        let width = self.width;
+        (0..self.cells.len()).map(move |i| Pos::new(i % width, i / width))
+    }
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.cells.iter_mut()
+    }
+    /// Every cell with its position, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = (Pos, &T)> {
+        self.positions().zip(self.cells.iter())
+    }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Pos, &mut T)> {
+        self.positions().zip(self.cells.iter_mut())
+    }
+
+    pub fn row(&self, y: usize) -> Option<&[T]> {
+        (y < self.height).then(|| &self.cells[y * self.width..(y + 1) * self.width])
+    }
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // `max` keeps `chunks` happy on a zero-width grid
+        self.cells.chunks(self.width.max(1))
+    }
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        let width = self.width;
+        let cells = if x < width { &self.cells[x..] } else { &[] };
+        cells.iter().step_by(width.max(1))
+    }
+    /// Cells inside the inclusive rectangle spanned by `from` and `to`, clipped to the grid.
+    pub fn region(&self, from: Pos, to: Pos) -> impl Iterator<Item = (Pos, &T)> {
+        let xs = from.x..=to.x.min(self.width.saturating_sub(1));
+        (from.y..=to.y.min(self.height.saturating_sub(1)))
+            .flat_map(move |y| xs.clone().map(move |x| Pos::new(x, y)))
+            .filter_map(|pos| Some((pos, self.get(pos)?)))
+    }
+
+    /// Orthogonal neighbours of `pos` that are on the grid.
+    pub fn neighbors(&self, pos: Pos) -> impl Iterator<Item = (Pos, Direction, &T)> {
+        pos.neighbors(self.size())
+            .map(|(n, dir)| (n, dir, &self[n]))
+    }
+    /// The up to 3x3 block centred on `pos`, including `pos`.
+    pub fn around_8(&self, pos: Pos) -> impl Iterator<Item = (Pos, &T)> {
+        pos.around_8(self.size()).map(|n| (n, &self[n]))
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+}
+
+impl<T> Index<Pos> for Grid<T> {
+    type Output = T;
+    fn index(&self, pos: Pos) -> &T {
+        match self.index_of(pos) {
+            Some(i) => &self.cells[i],
+            None => panic!("{pos} is outside of a {}x{} grid", self.width, self.height),
+        }
+    }
+}
+impl<T> IndexMut<Pos> for Grid<T> {
+    fn index_mut(&mut self, pos: Pos) -> &mut T {
+        match self.index_of(pos) {
+            Some(i) => &mut self.cells[i],
+            None => panic!("{pos} is outside of a {}x{} grid", self.width, self.height),
+        }
+    }
+}
+
+/// Nested rows, the same shape it is read from.
+impl<T: Serialize> Serialize for Grid<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.rows())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RaggedRows {
+    pub row: usize,
+    pub expected: usize,
+    pub found: usize,
+}
+impl Display for RaggedRows {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "row {} has {} cells, expected {}",
+            self.row, self.found, self.expected
+        )
+    }
+}
+impl std::error::Error for RaggedRows {}
+
+impl<T> TryFrom<Vec<Vec<T>>> for Grid<T> {
+    type Error = RaggedRows;
+    fn try_from(rows: Vec<Vec<T>>) -> Result<Self, Self::Error> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, Vec::len);
+        let mut cells = Vec::with_capacity(width * height);
+        for (row, values) in rows.into_iter().enumerate() {
+            if values.len() != width {
+                return Err(RaggedRows {
+                    row,
+                    expected: width,
+                    found: values.len(),
+                });
+            }
+            cells.extend(values);
+        }
+        Ok(Grid {
+            width,
+            height,
+            cells,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered() -> Grid<usize> {
+        Grid::from_fn((4, 3), |pos| pos.y * 10 + pos.x)
+    }
+
+    #[test]
+    fn deserializes_nested_rows() {
+        let grid: Grid<u8> = serde_json::from_str("[[1,2,3],[4,5,6]]").unwrap();
+        assert_eq!(grid.size(), (3, 2));
+        assert_eq!(grid[Pos::new(2, 1)], 6);
+        assert_eq!(grid.get(Pos::new(3, 0)), None);
+        assert!(serde_json::from_str::<Grid<u8>>("[[1,2],[3]]").is_err());
+        assert_eq!(serde_json::to_string(&grid).unwrap(), "[[1,2,3],[4,5,6]]");
+    }
+
+    #[test]
+    fn rows_columns_and_regions() {
+        let grid = numbered();
+        assert_eq!(grid.row(1), Some(&[10, 11, 12, 13][..]));
+        assert_eq!(grid.row(3), None);
+        assert_eq!(grid.rows().count(), 3);
+        assert_eq!(grid.column(2).copied().collect::<Vec<_>>(), [2, 12, 22]);
+        assert_eq!(grid.column(4).count(), 0);
+        let region: Vec<_> = grid
+            .region(Pos::new(2, 1), Pos::new(9, 9))
+            .map(|(_, &v)| v)
+            .collect();
+        assert_eq!(region, [12, 13, 22, 23]);
+    }
+
+    #[test]
+    fn neighbors_stay_on_the_grid() {
+        let grid = numbered();
+        let corner: Vec<_> = grid.neighbors(Pos::new(0, 0)).map(|(_, _, &v)| v).collect();
+        assert_eq!(corner, [1, 10]);
+        assert_eq!(grid.neighbors(Pos::new(1, 1)).count(), 4);
+        assert_eq!(grid.around_8(Pos::new(3, 2)).count(), 4);
+    }
+}
@@ -0,0 +1,248 @@
+use std::{collections::BTreeSet, fs, io, path::Path, sync::Arc};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    diff::BoardEvent,
+    game_types::{Direction, Effect, Element, Map, Pos, ScanShape},
+    packets::C2SPacket,
+};
+
+/// One of our in-game actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Move(Direction),
+    Look(Direction),
+    Search(Direction),
+    PutWall(Direction),
+}
+impl Action {
+    pub fn from_packet(packet: &C2SPacket) -> Option<Action> {
+        match *packet {
+            C2SPacket::MovePlayer(dir) => Some(Action::Move(dir)),
+            C2SPacket::Look(dir) => Some(Action::Look(dir)),
+            C2SPacket::Search(dir) => Some(Action::Search(dir)),
+            C2SPacket::PutWall(dir) => Some(Action::PutWall(dir)),
+            _ => None,
+        }
+    }
+    pub fn to_packet(self) -> C2SPacket {
+        match self {
+            Action::Move(dir) => C2SPacket::MovePlayer(dir),
+            Action::Look(dir) => C2SPacket::Look(dir),
+            Action::Search(dir) => C2SPacket::Search(dir),
+            Action::PutWall(dir) => C2SPacket::PutWall(dir),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TurnEvent {
+    Sent(Action),
+    /// What one of our scans showed, already placed on the board.
+    Observed {
+        from: Pos,
+        shape: ScanShape,
+        cells: Vec<(Pos, Element)>,
+    },
+    /// The `effect` of an `updata_board` that followed an opponent action.
+    OpponentEffect(Effect),
+    OpponentSeen(Pos),
+    Scores {
+        us: u32,
+        opponent: u32,
+    },
+    BoardChanged(BoardEvent),
+    /// Our board after an `updata_board` was applied.
+    Board(Arc<Map>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// `GameState::turns_left` when this happened.
+    pub turn: u32,
+    pub event: TurnEvent,
+}
+
+/// Entries per shared chunk, see [`History`].
+const CHUNK: usize = 64;
+
+/// A full chunk of entries and all the ones before it.
+#[derive(Debug)]
+struct Chunk {
+    entries: Box<[Entry]>,
+    prev: Option<Arc<Chunk>>,
+}
+
+/// Append-only log of everything that happened during a game.
+///
+/// Full chunks of entries are shared between clones, so cloning copies at most one
+/// chunk's worth however long the game has been going.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    sealed: Option<Arc<Chunk>>,
+    tail: Vec<Entry>,
+}
+impl History {
+    pub fn push(&mut self, turn: u32, event: TurnEvent) {
+        self.tail.push(Entry { turn, event });
+        if self.tail.len() >= CHUNK {
+            self.sealed = Some(Arc::new(Chunk {
+                entries: std::mem::take(&mut self.tail).into_boxed_slice(),
+                prev: self.sealed.take(),
+            }));
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.chunks().iter().map(|c| c.len()).sum::<usize>() + self.tail.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.sealed.is_none() && self.tail.is_empty()
+    }
+    /// Sealed chunks, oldest first.
+    fn chunks(&self) -> Vec<&[Entry]> {
+        let mut chunks = vec![];
+        let mut chunk = self.sealed.as_deref();
+        while let Some(c) = chunk {
+            chunks.push(&c.entries[..]);
+            chunk = c.prev.as_deref();
+        }
+        chunks.reverse();
+        chunks
+    }
+    /// Every entry, oldest first.
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.chunks().into_iter().flatten().chain(self.tail.iter())
+    }
+    /// Entries from at most `turns` turns before `turns_left`, oldest first.
+    pub fn recent(&self, turns: u32, turns_left: u32) -> impl Iterator<Item = &Entry> {
+        self.entries()
+            .skip_while(move |e| e.turn.saturating_sub(turns_left) >= turns)
+    }
+
+    /// Where and on which turn we last saw the opponent.
+    pub fn last_opponent_sighting(&self) -> Option<(u32, Pos)> {
+        self.entries().rev().find_map(|e| match e.event {
+            TurnEvent::OpponentSeen(pos) => Some((e.turn, pos)),
+            _ => None,
+        })
+    }
+    /// Cells covered by our own `Look`s and `Search`es within the last `turns` turns.
+    pub fn searched_cells(&self, turns: u32, turns_left: u32) -> BTreeSet<Pos> {
+        self.recent(turns, turns_left)
+            .filter_map(|e| match &e.event {
+                TurnEvent::Observed { shape, cells, .. } if *shape != ScanShape::Around => {
+                    Some(cells)
+                }
+                _ => None,
+            })
+            .flatten()
+            .map(|&(pos, _)| pos)
+            .collect()
+    }
+    /// The board after the most recent `updata_board` at or before `turn`.
+    pub fn board_at(&self, turn: u32) -> Option<&Map> {
+        self.entries().rev().find_map(|e| match &e.event {
+            TurnEvent::Board(map) if e.turn >= turn => Some(&**map),
+            _ => None,
+        })
+    }
+    pub fn last_board(&self) -> Option<&Map> {
+        self.board_at(0)
+    }
+    /// Every board event so far, oldest first.
+    pub fn board_events(&self) -> impl Iterator<Item = (u32, BoardEvent)> {
+        self.entries().filter_map(|e| match e.event {
+            TurnEvent::BoardChanged(event) => Some((e.turn, event)),
+            _ => None,
+        })
+    }
+    pub fn actions(&self) -> impl Iterator<Item = (u32, Action)> {
+        self.entries().filter_map(|e| match e.event {
+            TurnEvent::Sent(action) => Some((e.turn, action)),
+            _ => None,
+        })
+    }
+
+    /// Writes the log as JSON for looking at after the game.
+    pub fn export(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Written out as a flat list of entries.
+impl Serialize for History {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.entries())
+    }
+}
+impl<'de> Deserialize<'de> for History {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut history = History::default();
+        for entry in Vec::<Entry>::deserialize(deserializer)? {
+            history.push(entry.turn, entry.event);
+        }
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observed(shape: ScanShape, cells: &[(usize, usize)]) -> TurnEvent {
+        TurnEvent::Observed {
+            from: Pos::new(0, 0),
+            shape,
+            cells: cells
+                .iter()
+                .map(|&cell| (cell.into(), Element::Blank))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn queries_recent_turns() {
+        let mut history = History::default();
+        history.push(20, TurnEvent::OpponentSeen(Pos::new(4, 4)));
+        history.push(20, observed(ScanShape::Search(Direction::Right), &[(1, 0)]));
+        history.push(14, TurnEvent::Sent(Action::Look(Direction::Bottom)));
+        history.push(
+            14,
+            observed(ScanShape::Look(Direction::Bottom), &[(0, 2), (1, 2)]),
+        );
+        history.push(13, observed(ScanShape::Around, &[(0, 1)]));
+        history.push(12, TurnEvent::OpponentSeen(Pos::new(5, 4)));
+
+        assert_eq!(history.last_opponent_sighting(), Some((12, Pos::new(5, 4))));
+        let searched: Vec<_> = history.searched_cells(5, 10).into_iter().collect();
+        assert_eq!(searched, [Pos::new(0, 2), Pos::new(1, 2)]);
+        assert_eq!(history.searched_cells(20, 10).len(), 3);
+        assert_eq!(
+            history.actions().collect::<Vec<_>>(),
+            [(14, Action::Look(Direction::Bottom))]
+        );
+    }
+
+    #[test]
+    fn clones_share_full_chunks() {
+        let mut history = History::default();
+        for turn in (0..150).rev() {
+            history.push(turn, TurnEvent::Sent(Action::Move(Direction::Left)));
+        }
+        let copy = history.clone();
+        assert!(Arc::ptr_eq(
+            history.sealed.as_ref().unwrap(),
+            copy.sealed.as_ref().unwrap()
+        ));
+        assert_eq!(copy.tail.len(), 150 % CHUNK);
+        assert_eq!(copy.len(), 150);
+        let turns: Vec<u32> = copy.entries().map(|e| e.turn).collect();
+        assert_eq!(turns, (0..150).rev().collect::<Vec<_>>());
+        assert_eq!(copy.recent(5, 0).count(), 5);
+
+        let json = serde_json::to_string(&history).unwrap();
+        let back: History = serde_json::from_str(&json).unwrap();
+        assert!(back.entries().eq(history.entries()));
+    }
+}
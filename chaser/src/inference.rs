@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game_types::{Direction, Effect, SearchType, Side};
+
+/// Something the opponent must have done, deduced from an `updata_board` packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OpponentAction {
+    /// Their score went up, so they are standing on the heart they just collected.
+    PickedHeart { gained: u32 },
+    /// They spent their action on a look or search, so they didn't move this turn.
+    Scanned {
+        search: SearchType,
+        direction: Option<Direction>,
+    },
+}
+
+pub fn infer(
+    opponent: Side,
+    old_score: u32,
+    new_score: u32,
+    effect: Option<Effect>,
+) -> Vec<OpponentAction> {
+    let mut actions = vec![];
+    if new_score > old_score {
+        actions.push(OpponentAction::PickedHeart {
+            gained: new_score - old_score,
+        });
+    }
+    if let Some(Effect {
+        search,
+        player,
+        direction,
+    }) = effect
+        && player == opponent
+        && search != SearchType::AroundCurrent
+    {
+        actions.push(OpponentAction::Scanned { search, direction });
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_scores_and_effects() {
+        let effect = |player, search| {
+            Some(Effect {
+                search,
+                player,
+                direction: Some(Direction::Left),
+            })
+        };
+        assert_eq!(
+            infer(Side::Hot, 1, 3, effect(Side::Hot, SearchType::Direction)),
+            [
+                OpponentAction::PickedHeart { gained: 2 },
+                OpponentAction::Scanned {
+                    search: SearchType::Direction,
+                    direction: Some(Direction::Left),
+                },
+            ]
+        );
+        // our own scan, and the look around every player gets anyway
+        assert!(infer(Side::Hot, 1, 1, effect(Side::Cold, SearchType::AroundSide)).is_empty());
+        assert!(
+            infer(
+                Side::Hot,
+                1,
+                1,
+                effect(Side::Hot, SearchType::AroundCurrent)
+            )
+            .is_empty()
+        );
+        assert!(infer(Side::Hot, 1, 1, None).is_empty());
+    }
+}
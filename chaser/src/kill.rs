@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    belief::OpponentBelief,
+    game_types::{Direction, Element, Map, Pos},
+    nav::{Nav, UnknownCells},
+};
+
+/// A way to win by walling the opponent in: walk along `moves`, then put a wall towards
+/// `wall`, onto `wall_at`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Kill {
+    pub moves: Vec<Direction>,
+    pub wall: Direction,
+    pub wall_at: Pos,
+}
+impl Kill {
+    /// Our turns until the wall is down, the wall included.
+    pub fn turns(&self) -> u32 {
+        self.moves.len() as u32 + 1
+    }
+}
+
+/// Cells a single wall wins with against an opponent at `opp`: the opponent's own cell, and
+/// their last exit if they only have one left. Unknown cells might be open, so they count as
+/// exits.
+pub fn finishing_walls(map: &Map, opp: Pos) -> Vec<Pos> {
+    let mut walls = vec![opp];
+    let mut exits = opp
+        .neighbors(map.size())
+        .map(|(n, _)| n)
+        .filter(|&n| map.at(n) != Element::Wall);
+    if let (Some(exit), None) = (exits.next(), exits.next()) {
+        walls.push(exit);
+    }
+    walls
+}
+
+/// The quickest way for us to put a wall onto `wall_at`, walking only through cells we know
+/// are open and never onto `avoid` or `wall_at` itself.
+fn reach_wall(map: &Map, us: Pos, wall_at: Pos, avoid: Pos) -> Option<Kill> {
+    let blocked = [avoid, wall_at];
+    let nav = Nav::new(map)
+        .unknown(UnknownCells::Blocked)
+        .blacklist(&blocked);
+    let spots: Vec<Pos> = wall_at
+        .neighbors(map.size())
+        .map(|(n, _)| n)
+        .filter(|&n| n == us || nav.walkable(n))
+        .collect();
+    let (spot, moves) = nav.bfs(us, &spots)?;
+    Some(Kill {
+        moves,
+        wall: spot.direction_to(wall_at)?,
+        wall_at,
+    })
+}
+
+/// Every winning wall against an opponent standing still at `opp`, each with the quickest way
+/// there, quickest first.
+pub fn kills(map: &Map, us: Pos, opp: Pos) -> Vec<Kill> {
+    let mut kills: Vec<Kill> = finishing_walls(map, opp)
+        .into_iter()
+        .filter_map(|wall_at| reach_wall(map, us, wall_at, opp))
+        .collect();
+    kills.sort_by_key(Kill::turns);
+    kills
+}
+
+/// Like [`kills`] when we only have a belief about where the opponent is. Each wall comes
+/// with the probability it wins right away, considering every cell the opponent could be
+/// in with at least `min_prob`. Most likely first, then quickest.
+pub fn kills_by_belief(
+    map: &Map,
+    us: Pos,
+    belief: &OpponentBelief,
+    min_prob: f64,
+) -> Vec<(Kill, f64)> {
+    let mut chance: BTreeMap<Pos, f64> = BTreeMap::new();
+    for (opp, p) in belief.most_likely() {
+        if p < min_prob {
+            break;
+        }
+        for wall_at in finishing_walls(map, opp) {
+            *chance.entry(wall_at).or_default() += p;
+        }
+    }
+    let mut kills: Vec<(Kill, f64)> = chance
+        .into_iter()
+        .filter_map(|(wall_at, p)| Some((reach_wall(map, us, wall_at, wall_at)?, p)))
+        .collect();
+    kills.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.turns().cmp(&b.0.turns())));
+    kills
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> Map {
+        "\
+.....
+.#.#.
+.#.#.
+.....
+#....
+.#..."
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn walls_the_opponent_in() {
+        let map = map();
+        let opp = Pos::new(2, 1);
+        assert_eq!(finishing_walls(&map, opp), [opp]);
+        let opp = Pos::new(2, 0);
+        assert_eq!(finishing_walls(&map, opp).len(), 1);
+
+        let pocket: Map = "...\n#.#\n#.#".parse().unwrap();
+        let opp = Pos::new(1, 2);
+        assert_eq!(finishing_walls(&pocket, opp), [opp, Pos::new(1, 1)]);
+        let kills = kills(&pocket, Pos::new(1, 0), opp);
+        assert_eq!(kills.len(), 2);
+        assert_eq!(kills[0].wall_at, Pos::new(1, 1));
+        assert_eq!(kills[0].moves, []);
+        assert_eq!(kills[0].wall, Direction::Bottom);
+        assert_eq!(kills[0].turns(), 1);
+        // walling the opponent itself means stepping into the exit first
+        assert_eq!(kills[1].wall_at, opp);
+        assert_eq!(kills[1].moves, [Direction::Bottom]);
+    }
+
+    #[test]
+    fn plans_a_walk_before_the_wall() {
+        let map = map();
+        let kills = kills(&map, Pos::new(4, 0), Pos::new(2, 2));
+        assert_eq!(kills.len(), 1);
+        // over the top to (2, 1), which is closer than going round to (2, 3)
+        assert_eq!(kills[0].wall, Direction::Bottom);
+        assert_eq!(kills[0].turns(), 4);
+        assert_eq!(
+            crate::nav::follow(Pos::new(4, 0), &kills[0].moves),
+            Some(Pos::new(2, 1))
+        );
+    }
+
+    #[test]
+    fn weighs_kills_by_belief() {
+        let pocket: Map = "...\n#.#\n#.#".parse().unwrap();
+        let mut belief = OpponentBelief::certain(Pos::new(1, 2), pocket.size());
+        belief.propagate(&pocket);
+        // half at (1, 2) and half at (1, 1), a wall at (1, 1) wins either way
+        let kills = kills_by_belief(&pocket, Pos::new(1, 0), &belief, 0.1);
+        let (best, p) = &kills[0];
+        assert_eq!(best.wall_at, Pos::new(1, 1));
+        assert!((p - 1.0).abs() < 1e-9);
+        assert_eq!(best.turns(), 1);
+    }
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_types::{Element, Pos, SearchType},
+    grid::Grid,
+};
+
+/// When and how a cell was last observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sighting {
+    /// Value of `GameState::turns_left` at the time, so it counts down like the server's `turn`.
+    pub turn: u32,
+    /// `None` when the cell came from a full-information board instead of one of our scans.
+    pub scan: Option<SearchType>,
+    /// Whether the cell held something different the previous time we saw it.
+    pub changed: bool,
+}
+
+/// A cell that no longer holds what we had recorded for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapChange {
+    pub pos: Pos,
+    pub from: Element,
+    pub to: Element,
+    pub cause: ChangeCause,
+}
+impl MapChange {
+    /// Walls only ever appear through `PutWall`, and ours are already in the map.
+    pub fn is_opponent_wall(&self) -> bool {
+        self.to == Element::Wall && self.from != Element::Wall
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeCause {
+    /// A scan saw something other than what we had recorded.
+    Scan,
+    /// We tried to walk into the cell and stayed where we were.
+    BlockedMove,
+}
+/// Whether going from `from` to `to` is a change to the board itself rather than a player
+/// walking through or a cell we had never seen.
+pub fn is_board_change(from: Element, to: Element) -> bool {
+    fn terrain(e: Element) -> bool {
+        matches!(e, Element::Blank | Element::Wall | Element::Heart)
+    }
+    from != to && terrain(from) && terrain(to)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastSeen(Grid<Option<Sighting>>);
+impl LastSeen {
+    pub fn new(size: (usize, usize)) -> LastSeen {
+        LastSeen(Grid::new(size, None))
+    }
+
+    pub fn at(&self, pos: Pos) -> Option<Sighting> {
+        self.0.get(pos).copied().flatten()
+    }
+    pub fn mark(&mut self, pos: Pos, sighting: Sighting) -> bool {
+        self.0.set(pos, Some(sighting))
+    }
+    pub fn mark_all(&mut self, sighting: Sighting) {
+        self.0.fill(Some(sighting));
+    }
+    pub fn grid(&self) -> &Grid<Option<Sighting>> {
+        &self.0
+    }
+
+    /// Turns since the cell was last observed, `None` if it never was.
+    pub fn age(&self, pos: Pos, turns_left: u32) -> Option<u32> {
+        self.at(pos).map(|s| s.turn.saturating_sub(turns_left))
+    }
+    /// Cells sorted from never seen to most recently seen.
+    pub fn stalest(&self) -> Vec<Pos> {
+        let mut cells: Vec<_> = self.0.positions().collect();
+        // turns count down, so a higher turn is an older sighting
+        cells.sort_by_key(|&pos| std::cmp::Reverse(self.at(pos).map_or(u32::MAX, |s| s.turn)));
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_terrain_changes_count() {
+        assert!(is_board_change(Element::Blank, Element::Wall));
+        assert!(is_board_change(Element::Heart, Element::Blank));
+        assert!(!is_board_change(Element::Heart, Element::Heart));
+        // never seen, or a player walking through
+        assert!(!is_board_change(Element::Unknown, Element::Wall));
+        assert!(!is_board_change(Element::Hot, Element::Blank));
+        assert!(!is_board_change(Element::Blank, Element::Cold));
+    }
+}
@@ -1,5 +1,24 @@
+pub mod belief;
+pub mod bitboard;
 pub mod client;
+pub mod diff;
+pub mod dstar;
 pub mod game;
 pub mod game_types;
+pub mod grid;
+pub mod history;
+pub mod inference;
+pub mod kill;
+pub mod knowledge;
+pub mod mcts;
+pub mod minimax;
+pub mod nav;
 pub mod packets;
+pub mod route;
+pub mod safety;
+pub mod scan;
+pub mod shared;
+pub mod sim;
+pub mod territory;
 pub mod ui;
+pub mod validate;
@@ -0,0 +1,416 @@
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    belief::OpponentBelief,
+    bitboard::Bitboard,
+    game::GameState,
+    game_types::{Element, Map, Pos, Side},
+    history::Action,
+    minimax::{Weights, evaluate},
+    sim::{Outcome, Sim},
+};
+
+/// How playouts pick actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Playout {
+    /// Uniformly among the legal actions.
+    Random,
+    /// Takes winning walls and hearts when it can, never steps next to the opponent, and
+    /// otherwise walks around randomly.
+    #[default]
+    Greedy,
+}
+impl FromStr for Playout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Playout::Random),
+            "greedy" => Ok(Playout::Greedy),
+            e => Err(format!("unknown playout {e} (expected random or greedy)")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MctsConfig {
+    /// Iterations per search, each on a fresh determinization.
+    pub iterations: u32,
+    /// Stops early once this much time went by.
+    pub budget: Duration,
+    /// UCB1 exploration constant.
+    pub exploration: f64,
+    pub playout: Playout,
+    /// Plies a playout runs before the position is scored with [`evaluate`].
+    pub playout_depth: u32,
+}
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            iterations: 5000,
+            budget: Duration::from_millis(200),
+            exploration: 0.7,
+            playout: Playout::default(),
+            playout_depth: 20,
+        }
+    }
+}
+
+/// Turns what we know into full boards: the opponent is drawn from the belief, and every
+/// unknown cell becomes a wall or heart as often as they show up among the cells we've seen.
+#[derive(Debug, Clone)]
+pub struct Determinizer<'a> {
+    map: &'a Map,
+    board: Bitboard,
+    us: (Side, Pos),
+    opponent: Option<Pos>,
+    belief: &'a OpponentBelief,
+    scores: [u32; 2],
+    turns_left: u32,
+    wall_rate: f64,
+    heart_rate: f64,
+}
+impl<'a> Determinizer<'a> {
+    /// `opponent` is where we can see the opponent, if we can. `None` if `map` is too large
+    /// for a [`Bitboard`].
+    pub fn new(
+        map: &'a Map,
+        us: (Side, Pos),
+        opponent: Option<Pos>,
+        belief: &'a OpponentBelief,
+        scores: [u32; 2],
+        turns_left: u32,
+    ) -> Option<Determinizer<'a>> {
+        let known = map.values().filter(|&&e| e != Element::Unknown).count();
+        let rate = |elem| {
+            let count = map.values().filter(|&&e| e == elem).count();
+            if known == 0 {
+                0.0
+            } else {
+                count as f64 / known as f64
+            }
+        };
+        Some(Determinizer {
+            map,
+            board: Bitboard::from_map(map)?,
+            us,
+            opponent,
+            belief,
+            scores,
+            turns_left,
+            wall_rate: rate(Element::Wall),
+            heart_rate: rate(Element::Heart),
+        })
+    }
+    pub fn from_state(state: &'a GameState) -> Option<Determinizer<'a>> {
+        let us = &state.players.us;
+        Determinizer::new(
+            &state.map,
+            (us.side, us.pos),
+            state.players.opponent.pos,
+            &state.opponent_belief,
+            [us.score, state.players.opponent.score],
+            state.turns_left,
+        )
+    }
+
+    fn opponent(&self, rng: &mut fastrand::Rng) -> Pos {
+        if let Some(pos) = self.opponent {
+            return pos;
+        }
+        let mut left = rng.f64() * self.belief.total();
+        for (pos, &p) in self.belief.grid().iter() {
+            left -= p;
+            if p > 0.0 && left <= 0.0 {
+                return pos;
+            }
+        }
+        // nothing to go on, anywhere that isn't a known wall
+        let open: Vec<Pos> = self
+            .map
+            .iter()
+            .filter(|&(pos, &e)| e != Element::Wall && pos != self.us.1)
+            .map(|(pos, _)| pos)
+            .collect();
+        rng.choice(open).unwrap_or(self.us.1)
+    }
+    /// One board that could be the real one.
+    pub fn sample(&self, rng: &mut fastrand::Rng) -> Sim {
+        let opponent = self.opponent(rng);
+        let mut board = self.board;
+        let players = board.cell(opponent) | board.cell(self.us.1);
+        for i in (board.all() & !board.known & !players).iter() {
+            let roll = rng.f64();
+            if roll < self.wall_rate {
+                board.walls.insert(i);
+            } else if roll < self.wall_rate + self.heart_rate {
+                board.hearts.insert(i);
+            }
+        }
+        Sim::from_board(board, self.us, opponent, self.scores, self.turns_left)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    /// The action leading here and who took it, `None` at the root.
+    action: Option<(Action, Side)>,
+    children: Vec<usize>,
+    visits: u32,
+    /// Iterations in which this node's action was legal while its parent was selected from.
+    available: u32,
+    /// Summed rewards for whoever took `action`.
+    reward: f64,
+}
+impl Node {
+    fn new(action: Option<(Action, Side)>) -> Node {
+        Node {
+            action,
+            children: vec![],
+            visits: 0,
+            available: 1,
+            reward: 0.0,
+        }
+    }
+}
+
+/// What [`Mcts::search`] settled on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MctsResult {
+    pub action: Action,
+    pub visits: u32,
+    /// Average reward of `action`, from 0 for a sure loss to 1 for a sure win.
+    pub value: f64,
+    pub iterations: u32,
+}
+
+/// Information set Monte Carlo tree search: every iteration plays on a different
+/// determinization, and statistics are shared between all of them in one tree of our and
+/// the opponent's actions.
+pub struct Mcts {
+    pub config: MctsConfig,
+    pub weights: Weights,
+    rng: fastrand::Rng,
+    nodes: Vec<Node>,
+}
+impl Mcts {
+    pub fn new(config: MctsConfig) -> Mcts {
+        Mcts::with_rng(config, fastrand::Rng::new())
+    }
+    pub fn with_rng(config: MctsConfig, rng: fastrand::Rng) -> Mcts {
+        Mcts {
+            config,
+            weights: Weights::default(),
+            rng,
+            nodes: vec![],
+        }
+    }
+
+    /// The most visited of our actions. `None` if there is nothing to do.
+    pub fn search(&mut self, boards: &Determinizer) -> Option<MctsResult> {
+        let deadline = Instant::now() + self.config.budget;
+        self.nodes = vec![Node::new(None)];
+        let mut iterations = 0;
+        while iterations < self.config.iterations && Instant::now() < deadline {
+            let sim = boards.sample(&mut self.rng);
+            iterations += 1;
+            // walled in by the walls we made up, nothing to learn
+            if sim.outcome().is_none() {
+                self.iterate(sim);
+            }
+        }
+        let best = self.nodes[0]
+            .children
+            .iter()
+            .map(|&c| &self.nodes[c])
+            .max_by_key(|node| node.visits)?;
+        Some(MctsResult {
+            action: best.action?.0,
+            visits: best.visits,
+            value: best.reward / best.visits.max(1) as f64,
+            iterations,
+        })
+    }
+
+    fn iterate(&mut self, mut sim: Sim) {
+        let mut path = vec![0];
+        let mut node = 0;
+        while sim.outcome().is_none() {
+            let legal = sim.actions();
+            let untried: Vec<Action> = legal
+                .iter()
+                .copied()
+                .filter(|&a| {
+                    !self.nodes[node]
+                        .children
+                        .iter()
+                        .any(|&c| self.nodes[c].action.is_some_and(|(b, _)| a == b))
+                })
+                .collect();
+            if let Some(action) = self.rng.choice(untried) {
+                let child = self.nodes.len();
+                self.nodes.push(Node::new(Some((action, sim.to_move()))));
+                self.nodes[node].children.push(child);
+                sim.apply(action);
+                path.push(child);
+                break;
+            }
+            node = self.select(node, &legal);
+            sim.apply(self.nodes[node].action.expect("children have actions").0);
+            path.push(node);
+        }
+
+        let result = self.playout(&mut sim);
+        self.nodes[0].visits += 1;
+        for &n in &path[1..] {
+            let node = &mut self.nodes[n];
+            let (_, side) = node.action.expect("children have actions");
+            node.visits += 1;
+            node.reward += result(side);
+        }
+    }
+
+    /// UCB1 over the children whose actions are legal in this determinization.
+    fn select(&mut self, node: usize, legal: &[Action]) -> usize {
+        let children = self.nodes[node].children.clone();
+        let mut best = (f64::NEG_INFINITY, children[0]);
+        for c in children {
+            let child = &mut self.nodes[c];
+            let Some((action, _)) = child.action else {
+                continue;
+            };
+            if !legal.contains(&action) {
+                continue;
+            }
+            child.available += 1;
+            let visits = child.visits.max(1) as f64;
+            let ucb = child.reward / visits
+                + self.config.exploration * ((child.available as f64).ln() / visits).sqrt();
+            if ucb > best.0 {
+                best = (ucb, c);
+            }
+        }
+        best.1
+    }
+
+    /// Plays on until the game ends or `playout_depth` runs out, and returns the reward for
+    /// either side.
+    fn playout(&mut self, sim: &mut Sim) -> impl Fn(Side) -> f64 + use<> {
+        for _ in 0..self.config.playout_depth {
+            if sim.outcome().is_some() {
+                break;
+            }
+            let action = match self.config.playout {
+                Playout::Random => self.rng.choice(sim.actions()),
+                Playout::Greedy => self.greedy(sim),
+            };
+            let Some(action) = action else {
+                break;
+            };
+            sim.apply(action);
+        }
+        let outcome = sim.outcome();
+        let cold = evaluate(sim, Side::Cold, &self.weights);
+        move |side| match outcome {
+            Some(Outcome::Won(winner)) => f64::from(winner == side),
+            Some(Outcome::Draw) => 0.5,
+            None => {
+                let value = if side == Side::Cold { cold } else { -cold };
+                1.0 / (1.0 + (-value as f64 / 200.0).exp())
+            }
+        }
+    }
+
+    fn greedy(&mut self, sim: &Sim) -> Option<Action> {
+        let actions = sim.actions();
+        if let Some(&win) = actions.iter().find(|&&a| sim.wins_outright(a)) {
+            return Some(win);
+        }
+        let us = sim.pos(sim.to_move());
+        let opp = sim.pos(sim.to_move().other());
+        let size = sim.board().size();
+        let moves: Vec<(Action, Pos)> = actions
+            .iter()
+            .filter_map(|&a| match a {
+                Action::Move(dir) => Some((a, us.checked_step(dir, size)?)),
+                _ => None,
+            })
+            .filter(|&(_, to)| to.manhattan(opp) > 1)
+            .collect();
+        if let Some(&(heart, _)) = moves
+            .iter()
+            .find(|&&(_, to)| sim.board().at(to) == Element::Heart)
+        {
+            return Some(heart);
+        }
+        self.rng
+            .choice(moves)
+            .map(|(a, _)| a)
+            .or_else(|| self.rng.choice(actions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_types::Direction;
+
+    #[test]
+    fn samples_boards_consistent_with_what_we_know() {
+        let map: Map = "#.#.\n????\n????\n*.#.".parse().unwrap();
+        let belief = OpponentBelief::certain(Pos::new(3, 3), map.size());
+        let boards = Determinizer::new(
+            &map,
+            (Side::Cold, Pos::new(1, 0)),
+            None,
+            &belief,
+            [0, 0],
+            10,
+        )
+        .unwrap();
+        let mut rng = fastrand::Rng::with_seed(3);
+        let mut walls = 0;
+        for _ in 0..50 {
+            let sim = boards.sample(&mut rng);
+            assert_eq!(sim.pos(Side::Hot), Pos::new(3, 3));
+            assert_eq!(sim.board().at(Pos::new(0, 0)), Element::Wall);
+            assert_eq!(sim.board().at(Pos::new(0, 3)), Element::Heart);
+            walls += sim.board().walls.len() - 3;
+        }
+        // three walls in eight known cells
+        let rate = walls as f64 / (50.0 * 8.0);
+        assert!((0.25..0.5).contains(&rate), "{rate}");
+    }
+
+    #[test]
+    fn finds_the_winning_wall() {
+        // the opponent's only way out of (2, 2) is (2, 1), right below us
+        let map: Map = ".....\n.#.#.\n.#.#.\n..#..".parse().unwrap();
+        let belief = OpponentBelief::certain(Pos::new(2, 2), map.size());
+        let boards = Determinizer::new(
+            &map,
+            (Side::Cold, Pos::new(2, 0)),
+            None,
+            &belief,
+            [0, 0],
+            20,
+        )
+        .unwrap();
+        let config = MctsConfig {
+            iterations: 2000,
+            budget: Duration::from_secs(5),
+            ..MctsConfig::default()
+        };
+        let mut mcts = Mcts::with_rng(config, fastrand::Rng::with_seed(1));
+        let result = mcts.search(&boards).unwrap();
+        assert_eq!(result.action, Action::PutWall(Direction::Bottom));
+        assert!(result.value > 0.9);
+        assert_eq!(result.iterations, 2000);
+    }
+}
@@ -0,0 +1,353 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    game_types::{Element, Side},
+    history::Action,
+    sim::{Outcome, Sim},
+};
+
+/// Scores a finished game, shifted by the ply it ended on so quicker wins come first.
+const WIN: i32 = 1_000_000;
+const TABLE_SIZE: usize = 1 << 16;
+
+fn is_win(value: i32) -> bool {
+    value.abs() >= WIN / 2
+}
+/// Win scores count plies from the root, the table keeps them relative to the node they
+/// were found at so they still hold when it's reached at another ply or in a later search.
+fn to_table(value: i32, ply: u32) -> i32 {
+    match value {
+        v if !is_win(v) => v,
+        v if v > 0 => v + ply as i32,
+        v => v - ply as i32,
+    }
+}
+fn from_table(value: i32, ply: u32) -> i32 {
+    match value {
+        v if !is_win(v) => v,
+        v if v > 0 => v - ply as i32,
+        v => v + ply as i32,
+    }
+}
+
+/// How much each part of [`evaluate`] counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weights {
+    /// Per point of score lead.
+    pub score: i32,
+    /// Per open neighbour we have more than the opponent.
+    pub mobility: i32,
+    /// Paid by whoever is down to a single exit, one wall away from losing.
+    pub trap: i32,
+    /// Per step we are closer to our nearest heart than the opponent is to theirs.
+    pub heart: i32,
+}
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            score: 100,
+            mobility: 10,
+            trap: 60,
+            heart: 3,
+        }
+    }
+}
+
+/// How good the position looks for `side`, ignoring whether the game is over.
+pub fn evaluate(sim: &Sim, side: Side, weights: &Weights) -> i32 {
+    let opponent = side.other();
+    let mut value = 0;
+    value += weights.score * (sim.score(side) as i32 - sim.score(opponent) as i32);
+
+    let (ours, theirs) = (sim.exits(sim.pos(side)), sim.exits(sim.pos(opponent)));
+    value += weights.mobility * (ours as i32 - theirs as i32);
+    if ours <= 1 {
+        value -= weights.trap;
+    }
+    if theirs <= 1 {
+        value += weights.trap;
+    }
+
+    let board = sim.board();
+    let nearest = |side| {
+        board
+            .hearts
+            .iter()
+            .map(|i| board.pos(i).manhattan(sim.pos(side)) as i32)
+            .min()
+    };
+    if let (Some(ours), Some(theirs)) = (nearest(side), nearest(opponent)) {
+        value += weights.heart * (theirs - ours);
+    }
+    value
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    /// The search failed high, the value is at least this.
+    Lower,
+    /// The search failed low, the value is at most this.
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TableEntry {
+    hash: u64,
+    depth: u32,
+    value: i32,
+    bound: Bound,
+    best: Option<Action>,
+}
+
+/// What [`Minimax::search`] settled on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchResult {
+    pub action: Action,
+    /// From the point of view of the player to move.
+    pub value: i32,
+    /// Plies of the deepest search that finished in time.
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+/// Alpha-beta search over [`Sim`] with iterative deepening and a transposition table.
+pub struct Minimax {
+    /// Time allowed for one [`Minimax::search`].
+    pub budget: Duration,
+    pub max_depth: u32,
+    pub weights: Weights,
+    table: Vec<Option<TableEntry>>,
+    nodes: u64,
+    deadline: Instant,
+}
+impl Minimax {
+    pub fn new(budget: Duration) -> Minimax {
+        Minimax {
+            budget,
+            max_depth: 32,
+            weights: Weights::default(),
+            table: vec![None; TABLE_SIZE],
+            nodes: 0,
+            deadline: Instant::now(),
+        }
+    }
+
+    /// Searches deeper and deeper until the budget runs out. `None` if the game is over.
+    pub fn search(&mut self, sim: &Sim) -> Option<SearchResult> {
+        let mut sim = sim.clone();
+        self.deadline = Instant::now() + self.budget;
+        self.nodes = 0;
+        let mut actions = self.ordered(&sim);
+        let mut best = SearchResult {
+            action: *actions.first()?,
+            value: -WIN,
+            depth: 0,
+            nodes: 0,
+        };
+        for depth in 1..=self.max_depth {
+            let Some((action, value)) = self.root(&mut sim, &actions, depth) else {
+                break;
+            };
+            best = SearchResult {
+                action,
+                value,
+                depth,
+                nodes: self.nodes,
+            };
+            // a forced result won't change with more depth
+            if value.abs() >= WIN - self.max_depth as i32 {
+                break;
+            }
+            // try the best move first next time
+            actions.retain(|&a| a != action);
+            actions.insert(0, action);
+        }
+        best.nodes = self.nodes;
+        Some(best)
+    }
+
+    fn root(&mut self, sim: &mut Sim, actions: &[Action], depth: u32) -> Option<(Action, i32)> {
+        let mut alpha = -WIN - 1;
+        let mut best = None;
+        for &action in actions {
+            let undo = sim.apply(action);
+            let value = self
+                .negamax(sim, depth - 1, 1, -WIN - 1, -alpha)
+                .map(|v| -v);
+            sim.undo(undo);
+            let value = value?;
+            if best.is_none() || value > alpha {
+                alpha = value;
+                best = Some((action, value));
+            }
+        }
+        best
+    }
+
+    /// Value of `sim` for the player to move, `None` once the time is up.
+    fn negamax(
+        &mut self,
+        sim: &mut Sim,
+        depth: u32,
+        ply: u32,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> Option<i32> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && Instant::now() >= self.deadline {
+            return None;
+        }
+        match sim.outcome() {
+            Some(Outcome::Won(side)) if side == sim.to_move() => return Some(WIN - ply as i32),
+            Some(Outcome::Won(_)) => return Some(-WIN + ply as i32),
+            Some(Outcome::Draw) => return Some(0),
+            None => (),
+        }
+        if depth == 0 {
+            return Some(evaluate(sim, sim.to_move(), &self.weights));
+        }
+
+        let slot = sim.hash() as usize % TABLE_SIZE;
+        let entry = self.table[slot].filter(|e| e.hash == sim.hash());
+        if let Some(entry) = entry.filter(|e| e.depth >= depth) {
+            let value = from_table(entry.value, ply);
+            match entry.bound {
+                Bound::Exact => return Some(value),
+                Bound::Lower => alpha = alpha.max(value),
+                Bound::Upper => beta = beta.min(value),
+            }
+            if alpha >= beta {
+                return Some(value);
+            }
+        }
+
+        let mut actions = self.ordered(sim);
+        if let Some(best) = entry.and_then(|e| e.best)
+            && let Some(i) = actions.iter().position(|&a| a == best)
+        {
+            actions[..=i].rotate_right(1);
+        }
+        let original_alpha = alpha;
+        let mut best = (-WIN - 1, None);
+        for action in actions {
+            let undo = sim.apply(action);
+            let value = self.negamax(sim, depth - 1, ply + 1, -beta, -alpha);
+            sim.undo(undo);
+            let value = -value?;
+            if value > best.0 {
+                best = (value, Some(action));
+            }
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best.0 <= original_alpha {
+            Bound::Upper
+        } else if best.0 >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table[slot] = Some(TableEntry {
+            hash: sim.hash(),
+            depth,
+            value: to_table(best.0, ply),
+            bound,
+            best: best.1,
+        });
+        Some(best.0)
+    }
+
+    /// Likely good actions first: winning walls, hearts, other moves, other walls, scans.
+    fn ordered(&self, sim: &Sim) -> Vec<Action> {
+        let us = sim.pos(sim.to_move());
+        let size = sim.board().size();
+        let mut actions = sim.actions();
+        actions.sort_by_key(|&action| match action {
+            _ if sim.wins_outright(action) => 0,
+            Action::Move(dir) => {
+                let heart = us
+                    .checked_step(dir, size)
+                    .is_some_and(|to| sim.board().at(to) == Element::Heart);
+                if heart { 1 } else { 2 }
+            }
+            Action::PutWall(_) => 3,
+            Action::Look(_) | Action::Search(_) => 4,
+        });
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_types::{Direction, Map, Pos};
+
+    fn search(sim: &Sim) -> SearchResult {
+        let mut minimax = Minimax::new(Duration::from_secs(5));
+        minimax.max_depth = 4;
+        minimax.search(sim).unwrap()
+    }
+
+    #[test]
+    fn takes_the_win() {
+        // the opponent's only way out of (2, 2) is (2, 1), right below us
+        let map: Map = ".....\n.#.#.\n.#.#.\n..#..".parse().unwrap();
+        let sim = Sim::new(
+            &map,
+            (Side::Cold, Pos::new(2, 0)),
+            Pos::new(2, 2),
+            [0, 0],
+            20,
+        );
+        let result = search(&sim);
+        assert_eq!(result.action, Action::PutWall(Direction::Bottom));
+        assert_eq!(result.value, WIN - 1);
+    }
+
+    #[test]
+    fn table_keeps_wins_relative_to_the_node() {
+        // a win 4 plies below a node at ply 3 is 2 plies below the same node at ply 1
+        assert_eq!(from_table(to_table(WIN - 7, 3), 1), WIN - 5);
+        assert_eq!(from_table(to_table(-WIN + 7, 3), 1), -WIN + 5);
+        assert_eq!(from_table(to_table(250, 3), 1), 250);
+    }
+
+    #[test]
+    fn table_survives_between_searches() {
+        let map: Map = ".....\n.#.#.\n.#.#.\n..#..".parse().unwrap();
+        let sim = Sim::new(
+            &map,
+            (Side::Cold, Pos::new(2, 0)),
+            Pos::new(2, 2),
+            [0, 0],
+            20,
+        );
+        let mut minimax = Minimax::new(Duration::from_secs(5));
+        minimax.max_depth = 4;
+        let first = minimax.search(&sim).unwrap();
+        let again = minimax.search(&sim).unwrap();
+        assert_eq!(first.value, WIN - 1);
+        assert_eq!(again.value, first.value);
+    }
+
+    #[test]
+    fn leaves_the_heart_next_to_the_opponent() {
+        // taking the heart puts us next to the opponent, who then walls us
+        let map: Map = "..*..\n.....".parse().unwrap();
+        let sim = Sim::new(
+            &map,
+            (Side::Hot, Pos::new(1, 0)),
+            Pos::new(3, 0),
+            [0, 0],
+            20,
+        );
+        let result = search(&sim);
+        assert_ne!(result.action, Action::Move(Direction::Right));
+        assert!(result.value > -WIN + 10);
+        assert_eq!(result.depth, 4);
+    }
+}
@@ -0,0 +1,271 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+};
+
+use crate::{
+    game_types::{Direction, Element, Map, Pos},
+    grid::Grid,
+};
+
+/// How to treat cells we have never seen under fog of war.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownCells {
+    /// Walk through them like blank cells.
+    #[default]
+    Passable,
+    /// Never path through them.
+    Blocked,
+    /// Walkable, but each one costs this much on top of the normal cost.
+    Penalty(u32),
+}
+impl UnknownCells {
+    /// Base cost of stepping onto a cell holding `elem`, `None` if it can't be entered.
+    pub fn enter_cost(self, elem: Element) -> Option<u32> {
+        match (elem, self) {
+            (Element::Wall, _) | (Element::Unknown, UnknownCells::Blocked) => None,
+            (Element::Unknown, UnknownCells::Penalty(p)) => Some(1 + p),
+            _ => Some(1),
+        }
+    }
+}
+
+/// Shortest paths over a [`Map`] for one query's worth of settings.
+///
+/// Walls are never walkable. Every path starts at `from` even if that cell is blacklisted,
+/// so a bot can blacklist where it is standing to get pushed somewhere else.
+pub struct Nav<'a> {
+    map: &'a Map,
+    blacklist: &'a [Pos],
+    unknown: UnknownCells,
+    cost: Box<dyn Fn(Pos) -> u32 + 'a>,
+}
+impl<'a> Nav<'a> {
+    pub fn new(map: &'a Map) -> Nav<'a> {
+        Nav {
+            map,
+            blacklist: &[],
+            unknown: UnknownCells::default(),
+            cost: Box::new(|_| 1),
+        }
+    }
+    pub fn blacklist(mut self, cells: &'a [Pos]) -> Self {
+        self.blacklist = cells;
+        self
+    }
+    pub fn unknown(mut self, policy: UnknownCells) -> Self {
+        self.unknown = policy;
+        self
+    }
+    /// Cost of stepping onto a cell, 1 for every cell by default. Values below 1 are
+    /// treated as 1 so A* stays admissible.
+    pub fn cost(mut self, cost: impl Fn(Pos) -> u32 + 'a) -> Self {
+        self.cost = Box::new(cost);
+        self
+    }
+
+    pub fn walkable(&self, pos: Pos) -> bool {
+        self.map
+            .get(pos)
+            .and_then(|elem| self.unknown.enter_cost(elem))
+            .is_some()
+            && !self.blacklist.contains(&pos)
+    }
+    /// What it costs to step onto `pos`, `None` if we can't.
+    pub fn step_cost(&self, pos: Pos) -> Option<u32> {
+        if !self.walkable(pos) {
+            return None;
+        }
+        let base = self.unknown.enter_cost(self.map.at(pos))?;
+        Some((self.cost)(pos).max(1) + base - 1)
+    }
+    fn neighbors(&self, pos: Pos) -> impl Iterator<Item = Pos> + '_ {
+        pos.neighbors(self.map.size())
+            .map(|(n, _)| n)
+            .filter(|&n| self.walkable(n))
+    }
+
+    /// Fewest steps from `from` to the nearest of `targets`, ignoring the cost function.
+    pub fn bfs(&self, from: Pos, targets: &[Pos]) -> Option<(Pos, Vec<Direction>)> {
+        let mut parent = Grid::new(self.map.size(), None);
+        let mut queue = VecDeque::from([from]);
+        parent.set(from, Some(from));
+        while let Some(pos) = queue.pop_front() {
+            if targets.contains(&pos) {
+                return Some((pos, walk_back(&parent, pos)));
+            }
+            for n in self.neighbors(pos) {
+                if parent[n].is_none() {
+                    parent[n] = Some(pos);
+                    queue.push_back(n);
+                }
+            }
+        }
+        None
+    }
+    /// Cheapest path from `from` to `to` with A*.
+    pub fn path(&self, from: Pos, to: Pos) -> Option<Vec<Direction>> {
+        let search = self.search(from, |pos| pos == to, |pos| pos.manhattan(to) as u32);
+        search.reached.map(|pos| walk_back(&search.parent, pos))
+    }
+    /// Cheapest path from `from` to whichever of `targets` is cheapest to reach.
+    pub fn path_to_any(&self, from: Pos, targets: &[Pos]) -> Option<(Pos, Vec<Direction>)> {
+        let search = self.search(from, |pos| targets.contains(&pos), |_| 0);
+        search
+            .reached
+            .map(|pos| (pos, walk_back(&search.parent, pos)))
+    }
+    /// Cost of the cheapest path from `from` to every cell, `None` where unreachable.
+    pub fn distance_field(&self, from: Pos) -> Grid<Option<u32>> {
+        self.search(from, |_| false, |_| 0).cost
+    }
+    /// `result[i][j]` is the cost from `cells[i]` to `cells[j]`.
+    pub fn all_pairs(&self, cells: &[Pos]) -> Vec<Vec<Option<u32>>> {
+        cells
+            .iter()
+            .map(|&from| {
+                let field = self.distance_field(from);
+                cells
+                    .iter()
+                    .map(|&to| field.get(to).copied().flatten())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Dijkstra, or A* with a non-zero `heuristic`, stopping at the first cell `done` accepts.
+    fn search(
+        &self,
+        from: Pos,
+        done: impl Fn(Pos) -> bool,
+        heuristic: impl Fn(Pos) -> u32,
+    ) -> Search {
+        let size = self.map.size();
+        let mut search = Search {
+            cost: Grid::new(size, None),
+            parent: Grid::new(size, None),
+            reached: None,
+        };
+        if !from.in_bounds(size) {
+            return search;
+        }
+        search.cost[from] = Some(0);
+        search.parent[from] = Some(from);
+        let mut open = BinaryHeap::from([Reverse((heuristic(from), 0, from))]);
+        while let Some(Reverse((_, g, pos))) = open.pop() {
+            if search.cost[pos].is_some_and(|best| g > best) {
+                continue;
+            }
+            if done(pos) {
+                search.reached = Some(pos);
+                break;
+            }
+            for n in self.neighbors(pos) {
+                let Some(step) = self.step_cost(n) else {
+                    continue;
+                };
+                let next = g + step;
+                if search.cost[n].is_none_or(|best| next < best) {
+                    search.cost[n] = Some(next);
+                    search.parent[n] = Some(pos);
+                    open.push(Reverse((next + heuristic(n), next, n)));
+                }
+            }
+        }
+        search
+    }
+}
+
+struct Search {
+    cost: Grid<Option<u32>>,
+    parent: Grid<Option<Pos>>,
+    reached: Option<Pos>,
+}
+
+/// Follows `parent` links back from `to` to the cell that is its own parent.
+fn walk_back(parent: &Grid<Option<Pos>>, to: Pos) -> Vec<Direction> {
+    let mut path = vec![to];
+    let mut pos = to;
+    while let Some(prev) = parent[pos]
+        && prev != pos
+    {
+        path.push(prev);
+        pos = prev;
+    }
+    path.reverse();
+    directions(&path)
+}
+
+/// The steps that walk along `path`, which must be made of adjacent cells.
+pub fn directions(path: &[Pos]) -> Vec<Direction> {
+    path.windows(2)
+        .map(|w| {
+            w[0].direction_to(w[1])
+                .expect("path cells must be adjacent")
+        })
+        .collect()
+}
+
+/// Where `dirs` leads from `from`, `None` if it walks off the board.
+pub fn follow(from: Pos, dirs: &[Direction]) -> Option<Pos> {
+    dirs.iter().try_fold(from, |pos, &dir| pos.step(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> Map {
+        "\
+.....
+.###.
+...#.
+?#...
+....."
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn paths_are_in_walking_order() {
+        let map = map();
+        let nav = Nav::new(&map);
+        let from = Pos::new(0, 2);
+        let path = nav.path(from, Pos::new(4, 2)).unwrap();
+        assert_eq!(path.len(), 6);
+        assert_eq!(follow(from, &path), Some(Pos::new(4, 2)));
+        assert_eq!(nav.path(from, from), Some(vec![]));
+        assert_eq!(nav.path(from, Pos::new(1, 1)), None);
+    }
+
+    #[test]
+    fn policies_change_the_route() {
+        let map = map();
+        let from = Pos::new(0, 2);
+        let to = Pos::new(0, 4);
+        assert_eq!(Nav::new(&map).path(from, to).map(|p| p.len()), Some(2));
+        let around = Nav::new(&map).unknown(UnknownCells::Blocked).path(from, to);
+        assert_eq!(around.map(|p| p.len()), Some(6));
+        let cheap = Nav::new(&map).unknown(UnknownCells::Penalty(2));
+        assert_eq!(cheap.distance_field(from)[to], Some(4));
+        let expensive = Nav::new(&map).unknown(UnknownCells::Penalty(10));
+        assert_eq!(expensive.distance_field(from)[to], Some(6));
+        let blocked = [Pos::new(0, 3), Pos::new(2, 4)];
+        assert_eq!(Nav::new(&map).blacklist(&blocked).path(from, to), None);
+    }
+
+    #[test]
+    fn searches_agree() {
+        let map = map();
+        let nav = Nav::new(&map);
+        let from = Pos::new(2, 2);
+        let targets = [Pos::new(4, 4), Pos::new(4, 0)];
+        let (bfs_target, bfs) = nav.bfs(from, &targets).unwrap();
+        let (target, path) = nav.path_to_any(from, &targets).unwrap();
+        assert_eq!(bfs.len(), path.len());
+        assert_eq!(bfs_target, target);
+        let field = nav.distance_field(from);
+        assert_eq!(field[target], Some(path.len() as u32));
+        assert_eq!(nav.all_pairs(&targets)[0][1], Some(4));
+    }
+}
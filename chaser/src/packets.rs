@@ -21,7 +21,7 @@ pub enum C2SPacket {
     PutWall(Direction),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 #[allow(dead_code)]
 #[serde(rename_all = "snake_case", tag = "packet", content = "data")]
 pub enum S2CPacket {
@@ -0,0 +1,197 @@
+use crate::{
+    game::GameState,
+    game_types::{Direction, Element, Map, Pos},
+    nav::Nav,
+    safety,
+    territory::we_arrive_first,
+};
+
+/// One heart on an [`Itinerary`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stop {
+    pub heart: Pos,
+    /// Our turns from now until we step onto it.
+    pub arrive: u32,
+    /// The opponent's shortest walk there, if we know where they are.
+    pub opponent: Option<u32>,
+}
+
+/// Hearts to collect in order, and every step of the way there.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Itinerary {
+    pub stops: Vec<Stop>,
+    pub directions: Vec<Direction>,
+}
+impl Itinerary {
+    pub fn turns(&self) -> u32 {
+        self.directions.len() as u32
+    }
+}
+
+/// Orders known hearts into the route that collects the most of them before `turns_left`
+/// runs out, by real walking distance.
+///
+/// Hearts we couldn't step onto safely are left out, and so are hearts the opponent gets to
+/// before we would, by [`we_arrive_first`].
+pub struct RoutePlanner<'a> {
+    map: &'a Map,
+    us: Pos,
+    opponent: Option<Pos>,
+    turns_left: u32,
+    /// Leave out hearts the opponent could wall us in at within this many turns.
+    pub safety_turns: u32,
+    /// Longest itinerary considered.
+    pub max_stops: usize,
+    /// Next stops tried from each stop, nearest first.
+    pub branching: usize,
+}
+impl<'a> RoutePlanner<'a> {
+    pub fn new(map: &'a Map, us: Pos, opponent: Option<Pos>, turns_left: u32) -> RoutePlanner<'a> {
+        RoutePlanner {
+            map,
+            us,
+            opponent,
+            turns_left,
+            safety_turns: 2,
+            max_stops: 6,
+            branching: 6,
+        }
+    }
+    pub fn from_state(state: &'a GameState) -> RoutePlanner<'a> {
+        RoutePlanner::new(
+            &state.map,
+            state.players.us.pos,
+            state.players.opponent.pos,
+            state.turns_left,
+        )
+    }
+
+    pub fn plan(&self) -> Itinerary {
+        let hearts: Vec<Pos> = self
+            .map
+            .iter()
+            .filter(|&(pos, &elem)| {
+                elem == Element::Heart
+                    && safety::safe_to_step(self.map, pos, self.opponent, self.safety_turns)
+            })
+            .map(|(pos, _)| pos)
+            .collect();
+        let nav = Nav::new(self.map);
+        // ours first, then every heart
+        let mut cells = vec![self.us];
+        cells.extend(&hearts);
+        let dist = nav.all_pairs(&cells);
+        let opponent: Vec<Option<u32>> = match self.opponent {
+            Some(opp) => {
+                let field = nav.distance_field(opp);
+                hearts.iter().map(|&h| field[h]).collect()
+            }
+            None => vec![None; hearts.len()],
+        };
+
+        let mut search = Search {
+            planner: self,
+            dist: &dist,
+            opponent: &opponent,
+            route: vec![],
+            best: (0, 0, vec![]),
+        };
+        search.extend(0, 0);
+        let (_, _, order) = search.best;
+
+        let mut itinerary = Itinerary::default();
+        let mut at = self.us;
+        for i in order {
+            let heart = hearts[i];
+            let Some(path) = nav.path(at, heart) else {
+                break;
+            };
+            itinerary.directions.extend(path);
+            itinerary.stops.push(Stop {
+                heart,
+                arrive: itinerary.directions.len() as u32,
+                opponent: opponent[i],
+            });
+            at = heart;
+        }
+        itinerary
+    }
+}
+
+/// Depth-first over the nearest few hearts from each stop.
+struct Search<'a, 'b> {
+    planner: &'b RoutePlanner<'a>,
+    /// From [`Nav::all_pairs`], with us at index 0 and heart `i` at `i + 1`.
+    dist: &'b [Vec<Option<u32>>],
+    opponent: &'b [Option<u32>],
+    route: Vec<usize>,
+    /// Most hearts, then fewest turns, and the hearts in order.
+    best: (usize, u32, Vec<usize>),
+}
+impl Search<'_, '_> {
+    fn extend(&mut self, from: usize, turns: u32) {
+        let (stops, best_turns, _) = self.best;
+        if self.route.len() > stops || (self.route.len() == stops && turns < best_turns) {
+            self.best = (self.route.len(), turns, self.route.clone());
+        }
+        if self.route.len() >= self.planner.max_stops {
+            return;
+        }
+        let mut next: Vec<(u32, usize)> = (0..self.opponent.len())
+            .filter(|i| !self.route.contains(i))
+            .filter_map(|i| Some((turns + self.dist[from][i + 1]?, i)))
+            .filter(|&(arrive, i)| {
+                arrive <= self.planner.turns_left
+                    && self.opponent[i].is_none_or(|theirs| we_arrive_first(arrive, theirs))
+            })
+            .collect();
+        next.sort();
+        for (arrive, i) in next.into_iter().take(self.planner.branching) {
+            self.route.push(i);
+            self.extend(i + 1, arrive);
+            self.route.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::follow;
+
+    fn map() -> Map {
+        "\
+.....*.
+.#####.
+.......
+#####.#
+.*....."
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn orders_hearts_by_walking_distance() {
+        let map = map();
+        let us = Pos::new(2, 2);
+        let route = RoutePlanner::new(&map, us, None, 100).plan();
+        let hearts: Vec<Pos> = route.stops.iter().map(|s| s.heart).collect();
+        // (1, 4) is closer as the crow flies, but a long way round
+        assert_eq!(hearts, [Pos::new(5, 0), Pos::new(1, 4)]);
+        assert_eq!(follow(us, &route.directions), Some(Pos::new(1, 4)));
+        assert_eq!(route.stops[0].arrive, 7);
+        assert_eq!(route.turns(), 7 + 10);
+
+        let short = RoutePlanner::new(&map, us, None, 8).plan();
+        assert_eq!(short.stops.len(), 1);
+    }
+
+    #[test]
+    fn leaves_hearts_to_a_closer_opponent() {
+        let map = map();
+        let route = RoutePlanner::new(&map, Pos::new(2, 2), Some(Pos::new(3, 4)), 100).plan();
+        assert_eq!(route.stops.len(), 1);
+        assert_eq!(route.stops[0].heart, Pos::new(5, 0));
+        assert_eq!(route.stops[0].opponent, Some(8));
+    }
+}
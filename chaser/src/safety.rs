@@ -0,0 +1,245 @@
+use crate::{
+    game_types::{Element, Map, Pos},
+    grid::Grid,
+    nav::{Nav, UnknownCells},
+};
+
+/// Whether we can count on being able to leave through `pos`. Unknown cells might be walls,
+/// so they don't count.
+fn open(map: &Map, pos: Pos) -> bool {
+    !matches!(map.get(pos), None | Some(Element::Wall | Element::Unknown))
+}
+
+/// Neighbours of `pos` we could step to, the board edge counts as a wall.
+pub fn exits(map: &Map, pos: Pos) -> usize {
+    pos.neighbors(map.size())
+        .filter(|&(n, _)| open(map, n))
+        .count()
+}
+
+/// A corridor that only connects to the rest of the board at one end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadEnd {
+    /// First cell with more than two exits on the way out, `None` if the corridor is all
+    /// there is.
+    pub mouth: Option<Pos>,
+    /// Steps from the cell to the mouth.
+    pub depth: usize,
+}
+
+/// Where walking along a corridor took us.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Corridor {
+    /// Stopped at this cell, which ends the corridor or opens up, after this many steps.
+    Ends(Pos, usize),
+    /// Came back around to a cell already walked, so one wall can't close it off.
+    Loops,
+}
+
+/// Walks along a corridor from `from`, which was entered coming from `prev`, until it ends,
+/// opens up or loops back on itself.
+fn follow_corridor(map: &Map, mut prev: Pos, mut from: Pos) -> Corridor {
+    let mut visited = Grid::new(map.size(), false);
+    visited[prev] = true;
+    let mut steps = 1;
+    while exits(map, from) == 2 {
+        visited[from] = true;
+        let Some((next, _)) = from
+            .neighbors(map.size())
+            .find(|&(n, _)| n != prev && open(map, n))
+        else {
+            break;
+        };
+        if visited[next] {
+            return Corridor::Loops;
+        }
+        (prev, from) = (from, next);
+        steps += 1;
+    }
+    Corridor::Ends(from, steps)
+}
+
+/// Whether `pos` is in a dead-end corridor, which the opponent can close off with a single
+/// wall at its mouth.
+pub fn dead_end(map: &Map, pos: Pos) -> Option<DeadEnd> {
+    let mut ways = vec![];
+    for (n, _) in pos.neighbors(map.size()).filter(|&(n, _)| open(map, n)) {
+        match follow_corridor(map, pos, n) {
+            Corridor::Ends(end, steps) => ways.push((end, steps)),
+            Corridor::Loops => return None,
+        }
+    }
+    let opens_up = |&(end, _): &(Pos, usize)| exits(map, end) > 2;
+    match ways[..] {
+        [] => Some(DeadEnd {
+            mouth: None,
+            depth: 0,
+        }),
+        [way] if opens_up(&way) => Some(DeadEnd {
+            mouth: Some(way.0),
+            depth: way.1,
+        }),
+        [a, b] => match (opens_up(&a), opens_up(&b)) {
+            (true, true) => None,
+            (true, false) => Some(DeadEnd {
+                mouth: Some(a.0),
+                depth: a.1,
+            }),
+            (false, true) => Some(DeadEnd {
+                mouth: Some(b.0),
+                depth: b.1,
+            }),
+            (false, false) => Some(DeadEnd {
+                mouth: None,
+                depth: 0,
+            }),
+        },
+        [_] => Some(DeadEnd {
+            mouth: None,
+            depth: 0,
+        }),
+        _ => None,
+    }
+}
+
+/// Turns the opponent needs to wall `pos` in completely with one `PutWall`: walking next to
+/// the last exit and placing the wall. `None` if one wall isn't enough.
+///
+/// An unseen opponent could be anywhere, so it is assumed to be right next to the exit.
+pub fn seal_turns(map: &Map, pos: Pos, opponent: Option<Pos>) -> Option<u32> {
+    let mut open_exits = pos
+        .neighbors(map.size())
+        .map(|(n, _)| n)
+        .filter(|&n| open(map, n));
+    let Some(exit) = open_exits.next() else {
+        return Some(0);
+    };
+    if open_exits.next().is_some() {
+        return None;
+    }
+    let Some(opponent) = opponent else {
+        return Some(1);
+    };
+    // the opponent may walk through cells we haven't seen, and can't place the wall from
+    // the cell we are in
+    let blocked = [pos];
+    let field = Nav::new(map)
+        .unknown(UnknownCells::Passable)
+        .blacklist(&blocked)
+        .distance_field(opponent);
+    exit.neighbors(map.size())
+        .filter_map(|(n, _)| field.get(n).copied().flatten())
+        .min()
+        .map(|walk| walk + 1)
+}
+/// Whether the opponent can wall `pos` in within `turns` turns.
+pub fn can_seal_within(map: &Map, pos: Pos, opponent: Option<Pos>, turns: u32) -> bool {
+    seal_turns(map, pos, opponent).is_some_and(|t| t <= turns)
+}
+
+/// Whether stepping onto `pos` keeps us out of reach: it's a cell we know to be open, the
+/// opponent can't drop a wall onto it on their next turn, and they can't wall it in within
+/// `turns` turns.
+pub fn safe_to_step(map: &Map, pos: Pos, opponent: Option<Pos>, turns: u32) -> bool {
+    if !open(map, pos) {
+        return false;
+    }
+    if opponent.is_some_and(|opp| opp.manhattan(pos) <= 1) {
+        return false;
+    }
+    !can_seal_within(map, pos, opponent, turns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> Map {
+        "\
+...#.
+.#.#.
+.....
+##.##
+?..#."
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn counts_exits_conservatively() {
+        let map = map();
+        assert_eq!(exits(&map, Pos::new(2, 2)), 4);
+        assert_eq!(exits(&map, Pos::new(0, 0)), 2);
+        // the unknown cell might be a wall
+        assert_eq!(exits(&map, Pos::new(1, 4)), 1);
+        assert_eq!(exits(&map, Pos::new(4, 4)), 0);
+    }
+
+    #[test]
+    fn finds_dead_ends() {
+        let map = map();
+        assert_eq!(
+            dead_end(&map, Pos::new(4, 0)),
+            Some(DeadEnd {
+                mouth: Some(Pos::new(2, 2)),
+                depth: 4,
+            })
+        );
+        assert_eq!(
+            dead_end(&map, Pos::new(2, 4)),
+            Some(DeadEnd {
+                mouth: Some(Pos::new(2, 2)),
+                depth: 2,
+            })
+        );
+        assert_eq!(dead_end(&map, Pos::new(2, 2)), None);
+        // a loop has a way out on both sides
+        assert_eq!(dead_end(&map, Pos::new(2, 0)), None);
+    }
+
+    #[test]
+    fn corridors_that_loop_are_not_dead_ends() {
+        let ring: Map = "...\n.#.\n...".parse().unwrap();
+        assert_eq!(dead_end(&ring, Pos::new(0, 0)), None);
+        assert_eq!(dead_end(&ring, Pos::new(1, 0)), None);
+        let lollipop: Map = "...\n.#.\n...\n#.#".parse().unwrap();
+        assert_eq!(dead_end(&lollipop, Pos::new(1, 2)), None);
+        assert_eq!(dead_end(&lollipop, Pos::new(0, 0)), None);
+        // the stick still is one
+        assert_eq!(
+            dead_end(&lollipop, Pos::new(1, 3)),
+            Some(DeadEnd {
+                mouth: Some(Pos::new(1, 2)),
+                depth: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn sealing_needs_the_opponent_next_to_the_last_exit() {
+        let map = map();
+        let pocket = Pos::new(1, 4);
+        assert_eq!(seal_turns(&map, pocket, None), Some(1));
+        assert_eq!(seal_turns(&map, Pos::new(2, 2), None), None);
+        assert_eq!(seal_turns(&map, Pos::new(4, 4), None), Some(0));
+        assert_eq!(seal_turns(&map, pocket, Some(Pos::new(2, 2))), Some(2));
+        assert_eq!(seal_turns(&map, pocket, Some(Pos::new(4, 0))), Some(6));
+        assert!(!can_seal_within(&map, pocket, Some(Pos::new(4, 0)), 5));
+    }
+
+    #[test]
+    fn safe_steps() {
+        let map = map();
+        let opp = Some(Pos::new(4, 0));
+        assert!(safe_to_step(&map, Pos::new(2, 2), opp, 10));
+        assert!(safe_to_step(&map, Pos::new(1, 4), opp, 5));
+        assert!(!safe_to_step(&map, Pos::new(1, 4), opp, 10));
+        assert!(!safe_to_step(
+            &map,
+            Pos::new(2, 2),
+            Some(Pos::new(2, 1)),
+            10
+        ));
+        assert!(!safe_to_step(&map, Pos::new(0, 4), opp, 1));
+    }
+}
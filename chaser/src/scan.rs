@@ -0,0 +1,262 @@
+use crate::{
+    belief::OpponentBelief,
+    game::GameState,
+    game_types::{Direction, Element, Map, Pos, ScanShape},
+    history::Action,
+    knowledge::LastSeen,
+};
+
+/// What each thing a scan could show us is worth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanWeights {
+    /// Per cell we have never seen.
+    pub unknown: f64,
+    /// Per cell we haven't seen for at least `stale_after` turns.
+    pub stale: f64,
+    pub stale_after: u32,
+    /// For certainly spotting the opponent, scaled by how likely that is.
+    pub opponent: f64,
+    /// Per heart we expect to find.
+    pub heart: f64,
+}
+impl Default for ScanWeights {
+    fn default() -> Self {
+        ScanWeights {
+            unknown: 1.0,
+            stale: 0.5,
+            stale_after: 10,
+            opponent: 8.0,
+            heart: 3.0,
+        }
+    }
+}
+
+/// How much one `Look` or `Search` is expected to tell us.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanValue {
+    pub action: Action,
+    pub unknown: usize,
+    pub stale: usize,
+    /// Probability that the opponent is in one of the scanned cells.
+    pub opponent: f64,
+    /// Expected number of hearts among the cells we don't know to be hearts.
+    pub hearts: f64,
+    pub value: f64,
+}
+
+/// Rates our possible scans against the fog-of-war knowledge.
+#[derive(Debug, Clone)]
+pub struct ScanPlanner<'a> {
+    map: &'a Map,
+    last_seen: &'a LastSeen,
+    belief: &'a OpponentBelief,
+    from: Pos,
+    turns_left: u32,
+    pub weights: ScanWeights,
+    /// Whether we already see the opponent, so no scan is worth anything for spotting them.
+    pub opponent_known: bool,
+}
+impl<'a> ScanPlanner<'a> {
+    pub fn new(
+        map: &'a Map,
+        last_seen: &'a LastSeen,
+        belief: &'a OpponentBelief,
+        from: Pos,
+        turns_left: u32,
+    ) -> ScanPlanner<'a> {
+        ScanPlanner {
+            map,
+            last_seen,
+            belief,
+            from,
+            turns_left,
+            weights: ScanWeights::default(),
+            opponent_known: false,
+        }
+    }
+    pub fn from_state(state: &'a GameState) -> ScanPlanner<'a> {
+        let mut planner = ScanPlanner::new(
+            &state.map,
+            &state.last_seen,
+            &state.opponent_belief,
+            state.players.us.pos,
+            state.turns_left,
+        );
+        // the belief sits right on a visible opponent
+        planner.opponent_known = state.players.opponent.pos.is_some() || !state.fog_of_war;
+        planner
+    }
+
+    /// Share of the cells we've seen that hold a heart, our guess for any other cell.
+    fn heart_rate(&self) -> f64 {
+        let known = self
+            .map
+            .values()
+            .filter(|&&e| e != Element::Unknown)
+            .count();
+        let hearts = self.map.values().filter(|&&e| e == Element::Heart).count();
+        if known == 0 {
+            0.0
+        } else {
+            hearts as f64 / known as f64
+        }
+    }
+
+    pub fn rate(&self, action: Action) -> Option<ScanValue> {
+        let shape = match action {
+            Action::Look(dir) => ScanShape::Look(dir),
+            Action::Search(dir) => ScanShape::Search(dir),
+            _ => return None,
+        };
+        let heart_rate = self.heart_rate();
+        let stale_after = self.weights.stale_after.max(1);
+        let mut rating = ScanValue {
+            action,
+            unknown: 0,
+            stale: 0,
+            opponent: 0.0,
+            hearts: 0.0,
+            value: 0.0,
+        };
+        for pos in shape
+            .cells(self.from, self.map.size())
+            .into_iter()
+            .flatten()
+        {
+            if !self.opponent_known {
+                rating.opponent += self.belief.at(pos);
+            }
+            let elem = self.map.at(pos);
+            let age = self.last_seen.age(pos, self.turns_left);
+            if elem == Element::Unknown || age.is_none() {
+                rating.unknown += 1;
+                rating.hearts += heart_rate;
+            } else if let Some(age) = age
+                && elem != Element::Heart
+                && elem != Element::Wall
+            {
+                if age >= stale_after {
+                    rating.stale += 1;
+                }
+                // hearts can show up while we aren't looking
+                rating.hearts += heart_rate * age.min(stale_after) as f64 / stale_after as f64;
+            }
+        }
+        let w = &self.weights;
+        rating.value = rating.unknown as f64 * w.unknown
+            + rating.stale as f64 * w.stale
+            + rating.opponent.min(1.0) * w.opponent
+            + rating.hearts * w.heart;
+        Some(rating)
+    }
+    /// Every `Look` and `Search`, best first.
+    pub fn rate_all(&self) -> Vec<ScanValue> {
+        let mut ratings: Vec<ScanValue> = Direction::ALL
+            .into_iter()
+            .flat_map(|dir| [Action::Look(dir), Action::Search(dir)])
+            .filter_map(|action| self.rate(action))
+            .collect();
+        ratings.sort_by(|a, b| b.value.total_cmp(&a.value));
+        ratings
+    }
+    /// The best scan, if it is worth more than `threshold`.
+    pub fn best(&self, threshold: f64) -> Option<ScanValue> {
+        self.rate_all()
+            .into_iter()
+            .next()
+            .filter(|best| best.value > threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{OwnPlayer, Player, Players},
+        game_types::Side,
+        knowledge::Sighting,
+    };
+
+    #[test]
+    fn prefers_what_we_havent_seen() {
+        let map: Map = "\
+????.....
+????.....
+????.....
+????.*...
+????....."
+            .parse()
+            .unwrap();
+        let mut last_seen = LastSeen::new(map.size());
+        for (pos, &elem) in map.iter() {
+            if elem != Element::Unknown {
+                let sighting = Sighting {
+                    turn: 50,
+                    scan: None,
+                    changed: false,
+                };
+                last_seen.mark(pos, sighting);
+            }
+        }
+        let belief = OpponentBelief::certain(Pos::new(8, 2), map.size());
+        let from = Pos::new(4, 2);
+        let planner = ScanPlanner::new(&map, &last_seen, &belief, from, 48);
+
+        let ratings = planner.rate_all();
+        assert_eq!(ratings.len(), 8);
+        // the whole 3x3 to the left is unknown
+        assert_eq!(ratings[0].action, Action::Look(Direction::Left));
+        assert_eq!(ratings[0].unknown, 9);
+        let right = planner.rate(Action::Search(Direction::Right)).unwrap();
+        assert_eq!(right.unknown, 0);
+        assert_eq!(right.opponent, 1.0);
+        assert!(planner.rate(Action::Move(Direction::Left)).is_none());
+
+        assert!(planner.best(100.0).is_none());
+        // 30 turns later the known side has gone stale too
+        let planner = ScanPlanner::new(&map, &last_seen, &belief, from, 20);
+        let right = planner.rate(Action::Look(Direction::Right)).unwrap();
+        assert_eq!(right.stale, 8);
+    }
+
+    #[test]
+    fn no_point_scanning_a_visible_opponent() {
+        let map: Map = ".....\n.....\n..C..\n.....\n..H..".parse().unwrap();
+        let players = Players {
+            us: OwnPlayer {
+                name: "us".into(),
+                pos: Pos::new(2, 2),
+                score: 0,
+                side: Side::Cold,
+            },
+            opponent: Player {
+                name: "them".into(),
+                pos: Some(Pos::new(2, 4)),
+                score: 0,
+                side: Side::Hot,
+            },
+        };
+        let mut state = GameState::new("room".into(), map.size(), &map, players, 49, false);
+        state.last_seen.mark_all(Sighting {
+            turn: 50,
+            scan: None,
+            changed: false,
+        });
+        // the belief alone makes it look like the opponent is worth a look
+        let blind = ScanPlanner::new(
+            &state.map,
+            &state.last_seen,
+            &state.opponent_belief,
+            Pos::new(2, 2),
+            49,
+        );
+        assert_eq!(
+            blind.best(4.0).map(|s| s.action),
+            Some(Action::Look(Direction::Bottom))
+        );
+        let planner = ScanPlanner::from_state(&state);
+        assert!(planner.opponent_known);
+        assert!(planner.rate_all().iter().all(|s| s.opponent == 0.0));
+        assert!(planner.best(4.0).is_none());
+    }
+}
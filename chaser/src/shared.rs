@@ -0,0 +1,51 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+    mpsc::{self, Receiver, Sender},
+};
+
+use arc_swap::ArcSwap;
+use parking_lot::Mutex;
+
+use crate::game::GameState;
+
+/// The latest [`GameState`] published by the protocol thread. Readers get an immutable
+/// snapshot that stays valid however long they keep it, and never block the writer.
+pub struct SharedState {
+    current: ArcSwap<GameState>,
+    version: AtomicU64,
+    subscribers: Mutex<Vec<Sender<u64>>>,
+}
+impl SharedState {
+    pub fn new(state: GameState) -> SharedState {
+        SharedState {
+            current: ArcSwap::from_pointee(state),
+            version: AtomicU64::new(0),
+            subscribers: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn snapshot(&self) -> Arc<GameState> {
+        self.current.load_full()
+    }
+    /// Bumped on every [`SharedState::publish`].
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Replaces the snapshot and tells every subscriber about the new version.
+    pub fn publish(&self, state: GameState) {
+        self.current.store(Arc::new(state));
+        let version = self.version.fetch_add(1, Ordering::AcqRel) + 1;
+        self.subscribers
+            .lock()
+            .retain(|tx| tx.send(version).is_ok());
+    }
+    /// Receives the new version number after each [`SharedState::publish`]. Dropping the
+    /// receiver unsubscribes.
+    pub fn subscribe(&self) -> Receiver<u64> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().push(tx);
+        rx
+    }
+}
@@ -0,0 +1,396 @@
+use std::cmp::Ordering;
+
+use crate::{
+    bitboard::{Bitboard, MAX_CELLS},
+    game::GameState,
+    game_types::{Direction, Element, Map, Pos, Side},
+    history::Action,
+};
+
+/// How a simulated game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Won(Side),
+    Draw,
+}
+
+/// What [`Sim::apply`] needs to take an action back.
+#[derive(Debug, Clone, Copy)]
+pub struct Undo {
+    from: Pos,
+    cell: Option<(Pos, Element)>,
+    score: u32,
+    turns_left: u32,
+    outcome: Option<Outcome>,
+    hash: u64,
+}
+
+/// Our model of the rules, for searching ahead in-process.
+///
+/// Players take turns, each either moving, putting a wall next to themselves or scanning.
+/// Stepping onto a heart scores a point. Putting a wall onto the opponent wins, and so does
+/// leaving them with walls or the board edge on all four sides. Once `turns_left` runs out
+/// after the opponent's turn, the higher score wins.
+///
+/// Players aren't stored on the board, and unknown cells are treated as blank.
+#[derive(Debug, Clone)]
+pub struct Sim {
+    board: Bitboard,
+    pos: [Pos; 2],
+    score: [u32; 2],
+    us: Side,
+    to_move: Side,
+    turns_left: u32,
+    outcome: Option<Outcome>,
+    hash: u64,
+}
+
+fn index(side: Side) -> usize {
+    match side {
+        Side::Cold => 0,
+        Side::Hot => 1,
+    }
+}
+/// splitmix64, standing in for a table of random Zobrist keys.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+fn cell_key(board: &Bitboard, pos: Pos, what: u64) -> u64 {
+    let i = board.index(pos).expect("on the board");
+    mix(i as u64 * 8 + what)
+}
+const WALL: u64 = 0;
+const HEART: u64 = 1;
+const PLAYER: u64 = 2;
+const HOT_TO_MOVE: u64 = u64::MAX;
+fn turns_key(turns_left: u32) -> u64 {
+    mix(1 << 40 | turns_left as u64)
+}
+fn score_key(side: usize, score: u32) -> u64 {
+    mix(2 << 40 | (side as u64) << 32 | score as u64)
+}
+
+impl Sim {
+    /// `us` moves first. Panics if `map` has more than [`MAX_CELLS`] cells.
+    pub fn new(
+        map: &Map,
+        us: (Side, Pos),
+        opponent: Pos,
+        scores: [u32; 2],
+        turns_left: u32,
+    ) -> Sim {
+        let board = Bitboard::from_map(map).expect("board too large to simulate");
+        Sim::from_board(board, us, opponent, scores, turns_left)
+    }
+    /// Like [`Sim::new`], starting from a board that is already packed.
+    pub fn from_board(
+        mut board: Bitboard,
+        us: (Side, Pos),
+        opponent: Pos,
+        scores: [u32; 2],
+        turns_left: u32,
+    ) -> Sim {
+        board.known = board.all();
+        board.cold = None;
+        board.hot = None;
+        let (side, pos) = us;
+        let mut sim = Sim {
+            board,
+            pos: [pos; 2],
+            score: [0; 2],
+            us: side,
+            to_move: side,
+            turns_left,
+            outcome: None,
+            hash: 0,
+        };
+        sim.pos[index(side.other())] = opponent;
+        sim.score[index(side)] = scores[0];
+        sim.score[index(side.other())] = scores[1];
+        // a made-up board can have someone boxed in already, the last move decided it
+        if sim.walled_in(side) {
+            sim.outcome = Some(Outcome::Won(side.other()));
+        } else if sim.walled_in(side.other()) {
+            sim.outcome = Some(Outcome::Won(side));
+        }
+        sim.hash = sim.full_hash();
+        sim
+    }
+    /// The current board with us to move, taking the opponent from the belief when we can't
+    /// see them. `None` if we have no idea where they are, or the board is too large.
+    pub fn from_state(state: &GameState) -> Option<Sim> {
+        if state.map.width() * state.map.height() > MAX_CELLS {
+            return None;
+        }
+        let opponent = state.players.opponent.pos.or_else(|| {
+            let likely = state.opponent_belief.most_likely();
+            likely.first().map(|&(pos, _)| pos)
+        })?;
+        let us = &state.players.us;
+        Some(Sim::new(
+            &state.map,
+            (us.side, us.pos),
+            opponent,
+            [us.score, state.players.opponent.score],
+            state.turns_left,
+        ))
+    }
+
+    fn full_hash(&self) -> u64 {
+        let board = &self.board;
+        let mut hash = 0;
+        for i in board.walls.iter() {
+            hash ^= cell_key(board, board.pos(i), WALL);
+        }
+        for i in board.hearts.iter() {
+            hash ^= cell_key(board, board.pos(i), HEART);
+        }
+        for side in [Side::Cold, Side::Hot] {
+            hash ^= cell_key(board, self.pos(side), PLAYER + index(side) as u64);
+            hash ^= score_key(index(side), self.score(side));
+        }
+        if self.to_move == Side::Hot {
+            hash ^= mix(HOT_TO_MOVE);
+        }
+        hash ^ turns_key(self.turns_left)
+    }
+
+    pub fn board(&self) -> &Bitboard {
+        &self.board
+    }
+    pub fn us(&self) -> Side {
+        self.us
+    }
+    pub fn to_move(&self) -> Side {
+        self.to_move
+    }
+    pub fn pos(&self, side: Side) -> Pos {
+        self.pos[index(side)]
+    }
+    pub fn score(&self, side: Side) -> u32 {
+        self.score[index(side)]
+    }
+    pub fn turns_left(&self) -> u32 {
+        self.turns_left
+    }
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+    /// Identifies the position, for transposition tables.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Open neighbours of `pos`, the board edge counts as a wall.
+    pub fn exits(&self, pos: Pos) -> usize {
+        let board = &self.board;
+        (board.neighbors(board.cell(pos)) & !board.walls).len()
+    }
+    fn walled_in(&self, side: Side) -> bool {
+        self.exits(self.pos(side)) == 0
+    }
+
+    /// Everything the player to move can do. Scans don't change the board in this model, so
+    /// there is a single one standing in for all of them.
+    pub fn actions(&self) -> Vec<Action> {
+        if self.outcome.is_some() {
+            return vec![];
+        }
+        let pos = self.pos(self.to_move);
+        let mut actions = vec![];
+        for (n, dir) in pos.neighbors(self.board.size()) {
+            if self.board.at(n) != Element::Wall {
+                actions.push(Action::Move(dir));
+                actions.push(Action::PutWall(dir));
+            }
+        }
+        actions.push(Action::Search(Direction::Top));
+        actions
+    }
+
+    /// Whether `action` is a wall that wins on the spot, onto the opponent or their last
+    /// exit.
+    pub fn wins_outright(&self, action: Action) -> bool {
+        let Action::PutWall(dir) = action else {
+            return false;
+        };
+        let opp = self.pos(self.to_move.other());
+        let Some(at) = self.pos(self.to_move).checked_step(dir, self.board.size()) else {
+            return false;
+        };
+        at == opp
+            || (self.exits(opp) == 1
+                && at.manhattan(opp) == 1
+                && self.board.at(at) != Element::Wall)
+    }
+
+    /// Plays `action` for the player to move. It must be one of [`Sim::actions`].
+    pub fn apply(&mut self, action: Action) -> Undo {
+        let side = self.to_move;
+        let i = index(side);
+        let mut undo = Undo {
+            from: self.pos[i],
+            cell: None,
+            score: self.score[i],
+            turns_left: self.turns_left,
+            outcome: self.outcome,
+            hash: self.hash,
+        };
+        match action {
+            Action::Move(dir) => {
+                let from = self.pos[i];
+                let to = from
+                    .checked_step(dir, self.board.size())
+                    .expect("legal move");
+                self.hash ^= cell_key(&self.board, from, PLAYER + i as u64);
+                self.hash ^= cell_key(&self.board, to, PLAYER + i as u64);
+                self.pos[i] = to;
+                if self.board.at(to) == Element::Heart {
+                    self.hash ^= score_key(i, self.score[i]);
+                    self.score[i] += 1;
+                    self.hash ^= score_key(i, self.score[i]);
+                    self.board.set(to, Element::Blank);
+                    self.hash ^= cell_key(&self.board, to, HEART);
+                    undo.cell = Some((to, Element::Heart));
+                }
+            }
+            Action::PutWall(dir) => {
+                let at = self.pos[i]
+                    .checked_step(dir, self.board.size())
+                    .expect("legal wall");
+                if at == self.pos(side.other()) {
+                    self.outcome = Some(Outcome::Won(side));
+                } else {
+                    let old = self.board.at(at);
+                    if old == Element::Heart {
+                        self.hash ^= cell_key(&self.board, at, HEART);
+                    }
+                    self.board.set(at, Element::Wall);
+                    self.hash ^= cell_key(&self.board, at, WALL);
+                    undo.cell = Some((at, old));
+                }
+            }
+            Action::Look(_) | Action::Search(_) => (),
+        }
+
+        if self.outcome.is_none() {
+            if self.walled_in(side.other()) {
+                self.outcome = Some(Outcome::Won(side));
+            } else if self.walled_in(side) {
+                self.outcome = Some(Outcome::Won(side.other()));
+            }
+        }
+        if side != self.us {
+            self.hash ^= turns_key(self.turns_left);
+            self.turns_left = self.turns_left.saturating_sub(1);
+            self.hash ^= turns_key(self.turns_left);
+            if self.turns_left == 0 && self.outcome.is_none() {
+                let (a, b) = (self.score[0], self.score[1]);
+                self.outcome = Some(match a.cmp(&b) {
+                    Ordering::Greater => Outcome::Won(Side::Cold),
+                    Ordering::Less => Outcome::Won(Side::Hot),
+                    Ordering::Equal => Outcome::Draw,
+                });
+            }
+        }
+        self.to_move = side.other();
+        self.hash ^= mix(HOT_TO_MOVE);
+        undo
+    }
+    /// Takes back the action [`Sim::apply`] returned `undo` for.
+    pub fn undo(&mut self, undo: Undo) {
+        self.to_move = self.to_move.other();
+        let i = index(self.to_move);
+        self.pos[i] = undo.from;
+        self.score[i] = undo.score;
+        if let Some((pos, elem)) = undo.cell {
+            self.board.set(pos, elem);
+        }
+        self.turns_left = undo.turns_left;
+        self.outcome = undo.outcome;
+        self.hash = undo.hash;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_and_takes_back_turns() {
+        let map: Map = "*..\n.#.\n...".parse().unwrap();
+        let mut sim = Sim::new(
+            &map,
+            (Side::Cold, Pos::new(1, 0)),
+            Pos::new(2, 2),
+            [0, 0],
+            5,
+        );
+        let start = sim.hash();
+        assert_eq!(sim.actions().len(), 5);
+
+        let took_heart = sim.apply(Action::Move(Direction::Left));
+        assert_eq!(sim.score(Side::Cold), 1);
+        assert_eq!(sim.to_move(), Side::Hot);
+        let walled = sim.apply(Action::PutWall(Direction::Top));
+        assert_eq!(sim.board().at(Pos::new(2, 1)), Element::Wall);
+        assert_eq!(sim.turns_left(), 4);
+        assert_eq!(sim.hash(), sim.full_hash());
+
+        sim.undo(walled);
+        sim.undo(took_heart);
+        assert_eq!(sim.hash(), start);
+        assert_eq!(sim.board().to_map(), map);
+        assert_eq!(sim.score(Side::Cold), 0);
+    }
+
+    #[test]
+    fn hashes_the_scores() {
+        let map: Map = "*..\n.#.\n...".parse().unwrap();
+        let sim = |scores| {
+            Sim::new(
+                &map,
+                (Side::Cold, Pos::new(1, 0)),
+                Pos::new(2, 2),
+                scores,
+                5,
+            )
+        };
+        assert_ne!(sim([1, 0]).hash(), sim([0, 1]).hash());
+        assert_eq!(sim([1, 0]).hash(), sim([1, 0]).hash());
+    }
+
+    #[test]
+    fn ends_the_game() {
+        let map: Map = "...\n#.#\n#.#".parse().unwrap();
+        let mut sim = Sim::new(&map, (Side::Hot, Pos::new(1, 0)), Pos::new(1, 2), [0, 0], 5);
+        let undo = sim.apply(Action::PutWall(Direction::Bottom));
+        assert_eq!(sim.outcome(), Some(Outcome::Won(Side::Hot)));
+        assert!(sim.actions().is_empty());
+        sim.undo(undo);
+
+        let mut sim = Sim::new(&map, (Side::Hot, Pos::new(0, 0)), Pos::new(1, 2), [2, 3], 1);
+        sim.apply(Action::Search(Direction::Top));
+        assert_eq!(sim.outcome(), None);
+        sim.apply(Action::Search(Direction::Top));
+        assert_eq!(sim.outcome(), Some(Outcome::Won(Side::Cold)));
+    }
+
+    #[test]
+    fn starts_boxed_in() {
+        let map: Map = ".#.\n#..\n...".parse().unwrap();
+        let sim = Sim::new(&map, (Side::Hot, Pos::new(0, 0)), Pos::new(2, 2), [0, 0], 5);
+        assert_eq!(sim.outcome(), Some(Outcome::Won(Side::Cold)));
+        let sim = Sim::new(
+            &map,
+            (Side::Cold, Pos::new(2, 2)),
+            Pos::new(0, 0),
+            [0, 0],
+            5,
+        );
+        assert_eq!(sim.outcome(), Some(Outcome::Won(Side::Cold)));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{
+    belief::OpponentBelief,
+    game::GameState,
+    game_types::{Element, Map, Pos},
+    grid::Grid,
+    nav::Nav,
+};
+
+/// Who gets to a cell first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    Us,
+    Opponent,
+    /// As likely ours as theirs, which takes not knowing where the opponent is.
+    Contested,
+}
+
+/// Whether we get to a cell `ours` steps away before an opponent `theirs` steps away. We
+/// plan on our own turn, so we move first and a tie is ours.
+pub fn we_arrive_first(ours: u32, theirs: u32) -> bool {
+    ours <= theirs
+}
+
+/// Which cells each player reaches first by walking distance, a Voronoi split of the board.
+/// Ties are ours, see [`we_arrive_first`].
+#[derive(Debug, Clone)]
+pub struct Territory {
+    /// Probability the opponent gets to each cell first, `None` where neither of us can.
+    opponent_first: Grid<Option<f64>>,
+    hearts: Vec<Pos>,
+}
+impl Territory {
+    pub fn new(map: &Map, us: Pos, opponent: Pos) -> Territory {
+        Territory::from_positions(map, us, &[(opponent, 1.0)])
+    }
+    /// Weighs every cell the opponent is at least `min_prob` likely to be in.
+    pub fn from_belief(map: &Map, us: Pos, belief: &OpponentBelief, min_prob: f64) -> Territory {
+        let likely: Vec<(Pos, f64)> = belief
+            .most_likely()
+            .into_iter()
+            .take_while(|&(_, p)| p >= min_prob)
+            .collect();
+        Territory::from_positions(map, us, &likely)
+    }
+    pub fn from_state(state: &GameState) -> Territory {
+        let us = state.players.us.pos;
+        match state.players.opponent.pos {
+            Some(opp) => Territory::new(&state.map, us, opp),
+            None => Territory::from_belief(&state.map, us, &state.opponent_belief, 0.01),
+        }
+    }
+
+    fn from_positions(map: &Map, us: Pos, opponent: &[(Pos, f64)]) -> Territory {
+        let nav = Nav::new(map);
+        let ours = nav.distance_field(us);
+        let total: f64 = opponent.iter().map(|&(_, p)| p).sum();
+        let mut first = Grid::new(map.size(), 0.0);
+        let mut reachable = ours.map(Option::is_some);
+        for &(pos, p) in opponent {
+            let theirs = nav.distance_field(pos);
+            for (cell, share) in first.iter_mut() {
+                let chance = match (ours[cell], theirs[cell]) {
+                    (_, None) => 0.0,
+                    (Some(a), Some(b)) if we_arrive_first(a, b) => 0.0,
+                    _ => 1.0,
+                };
+                *share += chance * p / total;
+                reachable[cell] |= theirs[cell].is_some();
+            }
+        }
+        Territory {
+            opponent_first: Grid::from_fn(map.size(), |pos| reachable[pos].then_some(first[pos])),
+            hearts: map
+                .iter()
+                .filter(|&(_, &e)| e == Element::Heart)
+                .map(|(pos, _)| pos)
+                .collect(),
+        }
+    }
+
+    /// Probability the opponent gets to `pos` before us, `None` if neither of us can.
+    pub fn opponent_first(&self, pos: Pos) -> Option<f64> {
+        self.opponent_first.get(pos).copied().flatten()
+    }
+    pub fn claim(&self, pos: Pos) -> Option<Claim> {
+        let p = self.opponent_first(pos)?;
+        Some(if p < 0.5 {
+            Claim::Us
+        } else if p > 0.5 {
+            Claim::Opponent
+        } else {
+            Claim::Contested
+        })
+    }
+    /// How many cells have `claim`.
+    pub fn count(&self, claim: Claim) -> usize {
+        self.opponent_first
+            .positions()
+            .filter(|&pos| self.claim(pos) == Some(claim))
+            .count()
+    }
+    /// Cells still up for grabs: contested ones, and ones next to a cell the other side
+    /// claims.
+    pub fn frontier(&self) -> Vec<Pos> {
+        self.opponent_first
+            .positions()
+            .filter(|&pos| match self.claim(pos) {
+                None => false,
+                Some(Claim::Contested) => true,
+                Some(claim) => self
+                    .opponent_first
+                    .neighbors(pos)
+                    .any(|(n, _, _)| self.claim(n).is_some_and(|c| c != claim)),
+            })
+            .collect()
+    }
+    /// Hearts we and the opponent are expected to get to first.
+    pub fn hearts(&self) -> (f64, f64) {
+        self.hearts
+            .iter()
+            .filter_map(|&heart| self.opponent_first(heart))
+            .fold((0.0, 0.0), |(us, opp), p| (us + 1.0 - p, opp + p))
+    }
+    /// The scores each side ends up with if everyone collects the hearts in their territory.
+    pub fn secured(&self, scores: (u32, u32)) -> (f64, f64) {
+        let (us, opp) = self.hearts();
+        (scores.0 as f64 + us, scores.1 as f64 + opp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> Map {
+        "\
+*...*
+.#.#.
+.....
+##.##
+*...."
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn splits_the_board_by_walking_distance() {
+        let map = map();
+        let territory = Territory::new(&map, Pos::new(0, 2), Pos::new(4, 4));
+        assert_eq!(territory.claim(Pos::new(0, 0)), Some(Claim::Us));
+        // 3 steps for both, and we go first
+        assert_eq!(territory.claim(Pos::new(2, 3)), Some(Claim::Us));
+        assert_eq!(territory.claim(Pos::new(2, 4)), Some(Claim::Opponent));
+        assert_eq!(territory.claim(Pos::new(0, 4)), Some(Claim::Opponent));
+        assert_eq!(territory.claim(Pos::new(1, 1)), None);
+        // (4, 0) is 6 steps for us and 8 for them
+        assert_eq!(territory.hearts(), (2.0, 1.0));
+        assert_eq!(territory.secured((2, 0)), (4.0, 1.0));
+        let frontier = territory.frontier();
+        assert!(frontier.contains(&Pos::new(2, 3)));
+        assert!(frontier.contains(&Pos::new(2, 4)));
+        assert!(!frontier.contains(&Pos::new(2, 2)));
+        assert!(!frontier.contains(&Pos::new(0, 0)));
+        assert_eq!(territory.count(Claim::Contested), 0);
+        let claimed = [Claim::Us, Claim::Opponent, Claim::Contested].map(|c| territory.count(c));
+        assert_eq!(claimed.iter().sum::<usize>(), 19);
+    }
+
+    #[test]
+    fn weighs_where_the_opponent_might_be() {
+        let map = map();
+        let belief = OpponentBelief::uniform(&map);
+        let territory = Territory::from_belief(&map, Pos::new(0, 2), &belief, 0.0);
+        // closer from 3 of the 19 open cells, and a tie from 2 more doesn't count
+        let p = territory.opponent_first(Pos::new(0, 0)).unwrap();
+        assert!((p - 3.0 / 19.0).abs() < 1e-9);
+        let (us, opp) = territory.hearts();
+        assert!((us + opp - 3.0).abs() < 1e-9);
+    }
+}
@@ -6,25 +6,22 @@ mod ui_enabled {
     use egui::{Color32, RichText, Stroke};
     use egui::{FontFamily::Proportional, TextStyle};
 
-    use parking_lot::Mutex;
-    use std::cmp::{max, min};
-    use std::ops::RangeInclusive;
     use std::sync::Arc;
     use std::thread;
 
     use winit::platform::wayland::EventLoopBuilderExtWayland;
 
-    use crate::game::GameState;
-    use crate::game_types::{Direction, Effect, Element, Map, SearchType, Side};
+    use crate::game_types::{Effect, Element, Map, Pos, ScanShape};
+    use crate::shared::SharedState;
 
-    struct ChaserMonitor(Arc<Mutex<GameState>>);
+    struct ChaserMonitor(Arc<SharedState>);
 
-    pub fn start_ui(state: Arc<Mutex<GameState>>) {
+    pub fn start_ui(state: Arc<SharedState>) {
         let env = std::env::var("NO_UI");
         if env.is_err() {
             thread::spawn(move || {
                 let (room, name, opp_name) = {
-                    let info = state.lock();
+                    let info = state.snapshot();
                     (
                         info.room.clone(),
                         info.players.us.name.clone(),
@@ -42,83 +39,36 @@ mod ui_enabled {
                 eframe::run_native(
                     &format!("Chaser Room '{room}': {name} vs {opp_name}"),
                     native_options,
-                    Box::new(|_ctx| Ok(Box::new(ChaserMonitor(state)))),
+                    Box::new(|cc| {
+                        // only redraw when the game state actually changed
+                        let ctx = cc.egui_ctx.clone();
+                        let changes = state.subscribe();
+                        thread::spawn(move || {
+                            for _ in changes {
+                                ctx.request_repaint();
+                            }
+                        });
+                        Ok(Box::new(ChaserMonitor(state)))
+                    }),
                 )
                 .expect("egui crashed!");
             });
         }
     }
 
-    fn highlight_at(
-        pos: (usize, usize),
+    /// Cells covered by the scan in `effect`, with the colour to highlight them in.
+    fn scanned_cells(
         map_size: (usize, usize),
         map: &Map,
         effect: &Effect,
-    ) -> Option<Color32> {
-        fn range(
-            r_x: RangeInclusive<usize>,
-            r_y: RangeInclusive<usize>,
-        ) -> impl Iterator<Item = (usize, usize)> {
-            (r_x).flat_map(move |x| (r_y.clone()).map(move |y| (x, y)))
-        }
-        macro_rules! r {
-            ($pos: expr, $map_size: expr) => {
-                $pos.saturating_sub(1)..=min($pos + 1, $map_size - 1)
-            };
-        }
-
-        let Effect {
-            search,
-            player,
-            direction,
-        } = effect;
-        let expected = match player {
-            Side::Hot => Element::Hot,
-            Side::Cold => Element::Cold,
-        };
-
-        let mut check_range = match search {
-            SearchType::AroundCurrent => range(r!(pos.0, map_size.0), r!(pos.1, map_size.1)),
-            SearchType::AroundSide => match direction {
-                Some(Direction::Top) => range(r!(pos.0, map_size.0), r!(pos.1 + 2, map_size.1)),
-                Some(Direction::Bottom) => range(
-                    r!(pos.0, map_size.0),
-                    r!(pos.1.saturating_sub(2), map_size.1),
-                ),
-                Some(Direction::Left) => range(r!(pos.0 + 2, map_size.0), r!(pos.1, map_size.1)),
-                Some(Direction::Right) => range(
-                    r!(pos.0.saturating_sub(2), map_size.0),
-                    r!(pos.1, map_size.1),
-                ),
-
-                None => unreachable!(),
-            },
-            SearchType::Direction => match direction {
-                Some(Direction::Top) => {
-                    range(pos.0..=pos.0, pos.1 + 1..=min(pos.1 + 9, map_size.1 - 1))
-                }
-                Some(Direction::Bottom) => range(
-                    pos.0..=pos.0,
-                    max(0, pos.1.saturating_sub(9))..=pos.1.saturating_sub(1),
-                ),
-                Some(Direction::Left) => {
-                    range(pos.0 + 1..=min(pos.0 + 9, map_size.0 - 1), pos.1..=pos.1)
-                }
-                Some(Direction::Right) => range(
-                    max(0, pos.0.saturating_sub(9))..=pos.0.saturating_sub(1),
-                    pos.1..=pos.1,
-                ),
-
-                None => unreachable!(),
-            },
+    ) -> Option<([Option<Pos>; 9], Color32)> {
+        let shape = ScanShape::from_effect(effect)?;
+        let scanner = map.find_player(effect.player)?;
+        let color = match shape {
+            ScanShape::Around => Color32::from_rgb(55, 66, 47),
+            ScanShape::Look(_) | ScanShape::Search(_) => Color32::from_rgb(22, 62, 91),
         };
-
-        check_range
-            .any(|(x, y)| map.at(x, y) == expected)
-            .then_some(match search {
-                SearchType::AroundCurrent => Color32::from_rgb(55, 66, 47),
-                SearchType::AroundSide | SearchType::Direction => Color32::from_rgb(22, 62, 91),
-            })
+        Some((shape.cells(scanner, map_size), color))
     }
 
     impl eframe::App for ChaserMonitor {
@@ -128,9 +78,13 @@ mod ui_enabled {
                 style.text_styles = [(TextStyle::Button, FontId::new(24.0, Proportional))].into();
                 ui.style_mut().text_styles = style.text_styles;
 
-                let info = self.0.lock();
+                let info = self.0.snapshot();
                 let (map, (cols, rows), effect) = (&info.map, info.map_size, &info.effect);
 
+                let scanned = effect
+                    .as_ref()
+                    .and_then(|effect| scanned_cells((cols, rows), map, effect));
+
                 let available = ui.available_size();
 
                 // Compute uniform cell sizes.
@@ -151,7 +105,7 @@ mod ui_enabled {
                             egui::vec2(cell_w - 3.0, cell_h - 3.0),
                         );
 
-                        let elem = map.at(col, row);
+                        let elem = map.at(Pos::new(col, row));
                         let color = match elem {
                             Element::Blank => Color32::TRANSPARENT,
                             Element::Wall => Color32::WHITE,
@@ -159,6 +113,7 @@ mod ui_enabled {
                             Element::Cold => Color32::from_rgb(4, 165, 229),
                             Element::Hot => Color32::from_rgb(210, 15, 57),
                             Element::BothColdAndHot => Color32::WHITE,
+                            Element::Unknown => Color32::DARK_GRAY,
                         };
                         let border_color = match elem {
                             Element::Blank => Color32::TRANSPARENT,
@@ -167,19 +122,19 @@ mod ui_enabled {
                             Element::Cold => Color32::from_rgb(4, 165, 229),
                             Element::Hot => Color32::from_rgb(210, 15, 57),
                             Element::BothColdAndHot => Color32::TRANSPARENT,
+                            Element::Unknown => Color32::TRANSPARENT,
                         };
                         let mut btn =
                             egui::Button::new(RichText::new(elem.to_string()).color(color))
                                 .stroke(Stroke::new(1.0, border_color));
-                        if let Some(effect) = effect
-                            && let Some(color) = highlight_at((col, row), (cols, rows), map, effect)
+                        if let Some((cells, color)) = &scanned
+                            && cells.contains(&Some(Pos::new(col, row)))
                         {
-                            btn = btn.fill(color);
+                            btn = btn.fill(*color);
                         }
                         ui.put(cell_rect, btn);
                     }
                 }
-                ui.ctx().request_repaint();
             });
         }
     }
@@ -187,12 +142,11 @@ mod ui_enabled {
 
 #[cfg(not(feature = "ui"))]
 mod ui_disabled {
-    use crate::game::GameState;
-    use parking_lot::Mutex;
+    use crate::shared::SharedState;
     use std::sync::Arc;
 
     #[allow(dead_code)]
-    pub fn start_ui(_state: Arc<Mutex<GameState>>) {}
+    pub fn start_ui(_state: Arc<SharedState>) {}
 }
 
 #[cfg(not(feature = "ui"))]
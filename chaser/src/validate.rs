@@ -0,0 +1,257 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::GameState,
+    game_types::{Element, GameData, Map, Pos},
+    packets::{C2SPacket, S2CPacket},
+};
+
+/// Runs a fog-of-war tracker next to a game whose true board is known and checks every
+/// reconstruction against it.
+pub struct Validator {
+    shadow: GameState,
+    pub report: Report,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mismatch {
+    /// The tracker predicted our last action would leave us somewhere else than the board
+    /// put us, say a move into a wall it hadn't seen.
+    Position { tracked: Pos, actual: Pos },
+    /// A cell the tracker has seen holds something else. `age` is how many turns ago it was
+    /// seen, stale cells are expected to drift.
+    Cell {
+        pos: Pos,
+        tracked: Element,
+        actual: Element,
+        age: Option<u32>,
+    },
+    /// The tracker placed the opponent on the wrong cell.
+    Opponent { tracked: Pos, actual: Option<Pos> },
+    /// The opponent belief gave the opponent's real cell no probability at all.
+    BeliefExcluded { actual: Pos },
+}
+
+impl Mismatch {
+    /// A cell that simply changed after we last looked at it, rather than a tracking bug.
+    pub fn is_stale(&self) -> bool {
+        matches!(self, Mismatch::Cell { age, .. } if age.is_none_or(|age| age > 1))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Report {
+    pub turns: u32,
+    pub known_cells: usize,
+    pub correct_cells: usize,
+    /// Cells seen within the last turn, which should always be correct.
+    pub fresh_cells: usize,
+    pub fresh_correct: usize,
+    pub position_errors: u32,
+    pub opponent_errors: u32,
+    pub belief_errors: u32,
+}
+impl Report {
+    pub fn accuracy(&self) -> f64 {
+        ratio(self.correct_cells, self.known_cells)
+    }
+    pub fn fresh_accuracy(&self) -> f64 {
+        ratio(self.fresh_correct, self.fresh_cells)
+    }
+}
+fn ratio(a: usize, b: usize) -> f64 {
+    if b == 0 { 1.0 } else { a as f64 / b as f64 }
+}
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1}% of known cells correct ({}/{}), {:.1}% of freshly seen ones ({}/{}) over {} turns; \
+             {} position, {} opponent and {} belief errors",
+            self.accuracy() * 100.0,
+            self.correct_cells,
+            self.known_cells,
+            self.fresh_accuracy() * 100.0,
+            self.fresh_correct,
+            self.fresh_cells,
+            self.turns,
+            self.position_errors,
+            self.opponent_errors,
+            self.belief_errors,
+        )
+    }
+}
+
+impl Validator {
+    /// `truth` should be a full-information state fresh from `new_board`.
+    pub fn new(truth: &GameState) -> Validator {
+        Validator {
+            shadow: GameState::new(
+                truth.room.clone(),
+                truth.map_size,
+                &truth.map,
+                truth.players.clone(),
+                truth.turns_left,
+                true,
+            ),
+            report: Report::default(),
+        }
+    }
+    pub fn tracker(&self) -> &GameState {
+        &self.shadow
+    }
+
+    pub fn on_sent(&mut self, packet: &C2SPacket) {
+        self.shadow.on_sent(packet);
+    }
+    /// Feeds `packet` to the tracker, checking it against the board whenever the packet
+    /// carries one.
+    pub fn on_packet(&mut self, packet: &S2CPacket) -> Vec<Mismatch> {
+        // the board corrects the tracker's position as it comes in, so check the prediction
+        let predicted = self.shadow.players.us.pos;
+        self.shadow.on_packet(packet.clone());
+        if let S2CPacket::UpdateBoard(GameData { map_data, .. }) = packet {
+            self.compare(map_data, predicted)
+        } else {
+            vec![]
+        }
+    }
+
+    /// Checks the tracker against `truth`, and where it expected us to be against where we
+    /// are.
+    pub fn compare(&mut self, truth: &Map, predicted: Pos) -> Vec<Mismatch> {
+        let shadow = &self.shadow;
+        let report = &mut self.report;
+        let mut mismatches = vec![];
+        report.turns += 1;
+
+        let us = shadow.players.us.side;
+        if let Some(actual) = truth.find_player(us)
+            && actual != predicted
+        {
+            report.position_errors += 1;
+            mismatches.push(Mismatch::Position {
+                tracked: predicted,
+                actual,
+            });
+        }
+
+        let opp = truth.find_player(shadow.players.opponent.side);
+        if let Some(tracked) = shadow.players.opponent.pos
+            && Some(tracked) != opp
+        {
+            report.opponent_errors += 1;
+            mismatches.push(Mismatch::Opponent {
+                tracked,
+                actual: opp,
+            });
+        }
+        if let Some(actual) = opp
+            && shadow.opponent_belief.at(actual) == 0.0
+        {
+            report.belief_errors += 1;
+            mismatches.push(Mismatch::BeliefExcluded { actual });
+        }
+
+        for (pos, &tracked) in shadow.map.iter() {
+            let Some(actual) = truth.get(pos) else {
+                continue;
+            };
+            if tracked == Element::Unknown {
+                continue;
+            }
+            let age = shadow.last_seen.age(pos, shadow.turns_left);
+            let fresh = age.is_some_and(|age| age <= 1);
+            report.known_cells += 1;
+            if fresh {
+                report.fresh_cells += 1;
+            }
+            if tracked == actual {
+                report.correct_cells += 1;
+                if fresh {
+                    report.fresh_correct += 1;
+                }
+            } else {
+                mismatches.push(Mismatch::Cell {
+                    pos,
+                    tracked,
+                    actual,
+                    age,
+                });
+            }
+        }
+        mismatches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{OwnPlayer, Player, Players},
+        game_types::{Direction, Effect, SearchType, Side},
+    };
+
+    fn truth() -> GameState {
+        let map: Map = "#.*..\n.C#.H\n....#".parse().unwrap();
+        let players = Players {
+            us: OwnPlayer {
+                name: "us".into(),
+                pos: Pos::new(1, 1),
+                score: 0,
+                side: Side::Cold,
+            },
+            opponent: Player {
+                name: "them".into(),
+                pos: Some(Pos::new(4, 1)),
+                score: 0,
+                side: Side::Hot,
+            },
+        };
+        GameState::new("room".into(), map.size(), &map, players, 40, false)
+    }
+    fn update(map_data: &str, turn: u32) -> S2CPacket {
+        S2CPacket::UpdateBoard(GameData {
+            map_data: map_data.parse().unwrap(),
+            cool_score: 0,
+            hot_score: 0,
+            turn,
+            effect: Some(Effect {
+                search: SearchType::AroundCurrent,
+                player: Side::Cold,
+                direction: None,
+            }),
+        })
+    }
+    fn positions(mismatches: &[Mismatch]) -> Vec<Mismatch> {
+        mismatches
+            .iter()
+            .copied()
+            .filter(|m| matches!(m, Mismatch::Position { .. }))
+            .collect()
+    }
+
+    #[test]
+    fn catches_mispredicted_moves() {
+        let mut validator = Validator::new(&truth());
+        // the tracker hasn't seen the wall to our right
+        validator.on_sent(&C2SPacket::MovePlayer(Direction::Right));
+        let mismatches = validator.on_packet(&update("#.*..\n.C#.H\n....#", 39));
+        assert_eq!(
+            positions(&mismatches),
+            [Mismatch::Position {
+                tracked: Pos::new(2, 1),
+                actual: Pos::new(1, 1),
+            }]
+        );
+        assert_eq!(validator.tracker().players.us.pos, Pos::new(1, 1));
+
+        validator.on_sent(&C2SPacket::MovePlayer(Direction::Left));
+        let mismatches = validator.on_packet(&update("#.*..\nC.#.H\n....#", 38));
+        assert!(positions(&mismatches).is_empty());
+        assert_eq!(validator.report.position_errors, 1);
+        assert_eq!(validator.report.turns, 2);
+    }
+}
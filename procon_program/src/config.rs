@@ -0,0 +1,141 @@
+use std::{fs, path::Path};
+
+use chaser::mcts::{MctsConfig, Playout};
+use serde::{Deserialize, Serialize};
+
+/// Tuning for the astar and mcts strategies, read from a TOML file. Missing keys keep their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Chase the opponent once fewer turns than this are left.
+    pub charge: u32,
+    /// Chase the opponent whenever they are closer than this.
+    pub opp_range: usize,
+    /// How sure the fog-of-war tracker has to be before we chase an opponent we can't see.
+    pub opp_confidence: f64,
+    /// Scan instead of moving when a scan is worth this much more than the hearts per turn
+    /// our route collects, see [`ScanPlanner`](chaser::scan::ScanPlanner).
+    pub scan_value: f64,
+    /// Failed paths in a row before we call it a deadlock.
+    pub stuck_turns: u32,
+    /// Turns we wait one step away from the opponent for them to come to us.
+    pub skip_turns: u32,
+    /// How far from us the deadlock escape may go.
+    pub deadlock_radius: usize,
+    /// Skip hearts the opponent could wall us in at within this many turns.
+    pub safety_turns: u32,
+    /// Go for a wall that walls the opponent in if it takes at most this many of our turns.
+    pub kill_turns: u32,
+    /// UCB1 exploration constant for mcts.
+    pub exploration: f64,
+    /// How mcts playouts pick their moves.
+    pub playout: Playout,
+    /// Plies an mcts playout runs before the position is scored.
+    pub playout_depth: u32,
+}
+impl Default for Config {
+    fn default() -> Self {
+        let mcts = MctsConfig::default();
+        Config {
+            charge: 50,
+            opp_range: 3,
+            opp_confidence: 0.3,
+            scan_value: 4.0,
+            stuck_turns: 5,
+            skip_turns: 3,
+            deadlock_radius: 2,
+            safety_turns: 2,
+            kill_turns: 2,
+            exploration: mcts.exploration,
+            playout: mcts.playout,
+            playout_depth: mcts.playout_depth,
+        }
+    }
+}
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("couldn't read {}: {e}", path.display()))?;
+        toml::from_str(&text).map_err(|e| format!("couldn't parse {}: {e}", path.display()))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.opp_confidence) {
+            return Err(format!(
+                "opp_confidence is a probability, got {}",
+                self.opp_confidence
+            ));
+        }
+        if !self.scan_value.is_finite() {
+            return Err(format!(
+                "scan_value must be finite, got {}",
+                self.scan_value
+            ));
+        }
+        if !(self.exploration.is_finite() && self.exploration >= 0.0) {
+            return Err(format!(
+                "exploration must be finite and not negative, got {}",
+                self.exploration
+            ));
+        }
+        // a playout that never moves leaves nothing for the playout policy to do
+        if self.playout_depth == 0 {
+            return Err("playout_depth must be at least 1".to_string());
+        }
+        // the escape has to have somewhere to go that isn't where we are
+        if self.deadlock_radius == 0 {
+            return Err("deadlock_radius must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_defaults_and_rejects_typos() {
+        let config: Config = toml::from_str("charge = 30\nscan_value = 2.5").unwrap();
+        assert_eq!(config.charge, 30);
+        assert_eq!(config.scan_value, 2.5);
+        assert_eq!(config.opp_range, Config::default().opp_range);
+        assert!(toml::from_str::<Config>("chrage = 30").is_err());
+        let config: Config = toml::from_str("playout = \"random\"").unwrap();
+        assert_eq!(config.playout, Playout::Random);
+        assert!(toml::from_str::<Config>("playout = \"smart\"").is_err());
+        // what we print at startup reads back the same
+        let printed = toml::to_string(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&printed).unwrap(), config);
+    }
+
+    #[test]
+    fn validates_ranges() {
+        assert!(Config::default().validate().is_ok());
+        let bad = [
+            Config {
+                opp_confidence: 1.5,
+                ..Config::default()
+            },
+            Config {
+                scan_value: f64::NAN,
+                ..Config::default()
+            },
+            Config {
+                deadlock_radius: 0,
+                ..Config::default()
+            },
+            Config {
+                exploration: -1.0,
+                ..Config::default()
+            },
+            Config {
+                playout_depth: 0,
+                ..Config::default()
+            },
+        ];
+        for config in bad {
+            assert!(config.validate().is_err(), "{config:?}");
+        }
+    }
+}
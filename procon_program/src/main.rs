@@ -1,19 +1,24 @@
-#![feature(iter_map_windows)]
-
-use std::{cmp::min, ops::RangeInclusive};
+use std::{cmp::min, ops::RangeInclusive, path::PathBuf, process, str::FromStr, time::Duration};
 
 use argh::FromArgs;
 use chaser::{
-    game::{ChaserGame, ChaserHandle},
-    game_types::{Direction, Element, Map},
+    dstar::DStarLite,
+    game::{ChaserGame, ChaserHandle, FogOfWar, GameState},
+    game_types::{Direction, Element, Pos},
+    kill,
+    mcts::{Determinizer, Mcts, MctsConfig, Playout},
+    minimax::Minimax,
+    nav::{Nav, UnknownCells},
     packets::C2SPacket,
+    route::RoutePlanner,
+    safety,
+    scan::ScanPlanner,
+    sim::Sim,
+    territory::Territory,
 };
-use pathfinding::prelude::astar;
-
-const CHARGE: u32 = 50;
-const OPP_RANGE: usize = 3;
+use config::Config;
 
-type Point = (usize, usize);
+mod config;
 
 #[derive(FromArgs)]
 /// Options for the client.
@@ -27,137 +32,333 @@ struct Options {
     /// server url
     #[argh(option)]
     server: Option<String>,
+    /// fog of war mode: auto (default), on or off
+    #[argh(option, default = "FogOfWar::Auto")]
+    fog_of_war: FogOfWar,
+    /// how to pick moves: astar (default), minimax or mcts
+    #[argh(option, default = "Strategy::Astar")]
+    strategy: Strategy,
+    /// milliseconds the minimax and mcts strategies may think per turn
+    #[argh(option, default = "200")]
+    budget: u64,
+    /// most mcts iterations per turn
+    #[argh(option, default = "5000")]
+    iterations: u32,
+    /// TOML file to read the astar and mcts tuning from, overridden by the options below
+    #[argh(option)]
+    config: Option<PathBuf>,
+    /// chase the opponent once fewer turns than this are left
+    #[argh(option)]
+    charge: Option<u32>,
+    /// chase the opponent whenever they are closer than this
+    #[argh(option)]
+    opp_range: Option<usize>,
+    /// how sure we have to be where an unseen opponent is to chase them
+    #[argh(option)]
+    opp_confidence: Option<f64>,
+    /// scan instead of moving when a scan is worth this much more than the move
+    #[argh(option)]
+    scan_value: Option<f64>,
+    /// failed paths in a row before we call it a deadlock
+    #[argh(option)]
+    stuck_turns: Option<u32>,
+    /// turns to wait one step away from the opponent
+    #[argh(option)]
+    skip_turns: Option<u32>,
+    /// how far from us the deadlock escape may go
+    #[argh(option)]
+    deadlock_radius: Option<usize>,
+    /// skip hearts the opponent could wall us in at within this many turns
+    #[argh(option)]
+    safety_turns: Option<u32>,
+    /// wall the opponent in if it takes at most this many turns
+    #[argh(option)]
+    kill_turns: Option<u32>,
+    /// mcts exploration constant
+    #[argh(option)]
+    exploration: Option<f64>,
+    /// how mcts playouts pick moves: random or greedy
+    #[argh(option)]
+    playout: Option<Playout>,
+    /// plies an mcts playout runs before scoring the position
+    #[argh(option)]
+    playout_depth: Option<u32>,
+}
+impl Options {
+    /// The config file, or the defaults, with the command line on top.
+    fn config(&self) -> Result<Config, String> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        fn set<T: Copy>(field: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        set(&mut config.charge, self.charge);
+        set(&mut config.opp_range, self.opp_range);
+        set(&mut config.opp_confidence, self.opp_confidence);
+        set(&mut config.scan_value, self.scan_value);
+        set(&mut config.stuck_turns, self.stuck_turns);
+        set(&mut config.skip_turns, self.skip_turns);
+        set(&mut config.deadlock_radius, self.deadlock_radius);
+        set(&mut config.safety_turns, self.safety_turns);
+        set(&mut config.kill_turns, self.kill_turns);
+        set(&mut config.exploration, self.exploration);
+        set(&mut config.playout, self.playout);
+        set(&mut config.playout_depth, self.playout_depth);
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+enum Strategy {
+    Astar,
+    Minimax,
+    Mcts,
+}
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "astar" => Ok(Strategy::Astar),
+            "minimax" => Ok(Strategy::Minimax),
+            "mcts" => Ok(Strategy::Mcts),
+            e => Err(format!(
+                "unknown strategy {e} (expected astar, minimax or mcts)"
+            )),
+        }
+    }
 }
 
 fn main() {
-    let Options { room, name, server } = argh::from_env();
+    let options: Options = argh::from_env();
+    let config = options.config().unwrap_or_else(|e| {
+        eprintln!("bad config: {e}");
+        process::exit(1);
+    });
+    println!("{}", toml::to_string(&config).expect("config serializes"));
+    let Options {
+        room,
+        name,
+        server,
+        fog_of_war,
+        strategy,
+        budget,
+        iterations,
+        ..
+    } = options;
 
-    let handle = if let Some(server) = server {
-        ChaserGame::join_url(
-            server,
-            name.unwrap_or("crystal".to_string()),
-            room.unwrap_or("Tornament2".to_string()),
-        )
-    } else {
-        ChaserGame::join(
-            name.unwrap_or("crystal".to_string()),
-            room.unwrap_or("Tornament2".to_string()),
-        )
-    };
+    let handle = ChaserGame::join_with(
+        server.unwrap_or("http://localhost:3000".to_string()),
+        name.unwrap_or("crystal".to_string()),
+        room.unwrap_or("Tornament2".to_string()),
+        fog_of_war,
+    );
     // handle.send(C2SPacket::MovePlayer(Direction::Left));
     // handle.send(C2SPacket::MovePlayer(Direction::Left));
     //
-    pathfind_astar(handle);
+    match strategy {
+        Strategy::Astar => pathfind_astar(handle, &config),
+        Strategy::Minimax => minimax(handle, Duration::from_millis(budget)),
+        Strategy::Mcts => mcts(
+            handle,
+            MctsConfig {
+                iterations,
+                budget: Duration::from_millis(budget),
+                exploration: config.exploration,
+                playout: config.playout,
+                playout_depth: config.playout_depth,
+            },
+        ),
+    }
     // search_test(handle);
 }
 
 enum TargetState {
     Searching,
-    Wandering(Point),
-    FixDeadlock(Point),
-    Heart(Point),
-    Opponent(Point),
+    Wandering(Pos),
+    FixDeadlock(Pos),
+    Heart(Pos),
+    Opponent(Pos),
 }
 
-fn dist(s: Point, t: Point) -> usize {
-    s.0.abs_diff(t.0) + s.1.abs_diff(t.1)
-}
 fn bounds_ranges(
-    around: Point,
-    size: Point,
+    around: Pos,
+    size: (usize, usize),
     radius: usize,
 ) -> (RangeInclusive<usize>, RangeInclusive<usize>) {
     (
-        around.0.saturating_sub(radius)..=min(size.0 - 1, around.0 + radius),
-        around.1.saturating_sub(radius)..=min(size.1 - 1, around.1 + radius),
+        around.x.saturating_sub(radius)..=min(size.0 - 1, around.x + radius),
+        around.y.saturating_sub(radius)..=min(size.1 - 1, around.y + radius),
     )
 }
-fn pathfind_astar(handle: ChaserHandle) {
+/// Runs `f` once on each of our turns, after the opponent's `updata_board`.
+fn each_turn(handle: ChaserHandle, mut f: impl FnMut(&ChaserHandle, &GameState)) {
+    let mut answered = None;
+    ChaserGame::run_loop(true, handle, |handle| {
+        let i = handle.info();
+        let our_turn = i
+            .effect
+            .is_none_or(|effect| effect.player != i.players.us.side);
+        if our_turn && answered != Some(i.turns_left) {
+            answered = Some(i.turns_left);
+            f(handle, &i);
+        }
+    });
+}
+fn minimax(handle: ChaserHandle, budget: Duration) {
+    let mut search = Minimax::new(budget);
+    each_turn(handle, |handle, i| {
+        let Some(sim) = Sim::from_state(i) else {
+            // nothing to search without the opponent, go looking for them
+            if let Some(scan) = ScanPlanner::from_state(i).best(0.0) {
+                handle.send(scan.action.to_packet());
+            }
+            return;
+        };
+        if let Some(result) = search.search(&sim) {
+            println!(
+                "minimax: {:?} ({} at depth {}, {} nodes)",
+                result.action, result.value, result.depth, result.nodes
+            );
+            handle.send(result.action.to_packet());
+        }
+    });
+}
+fn mcts(handle: ChaserHandle, config: MctsConfig) {
+    let mut search = Mcts::new(config);
+    each_turn(handle, |handle, i| {
+        let Some(boards) = Determinizer::from_state(i) else {
+            // too big to simulate, scan for the opponent instead
+            if let Some(scan) = ScanPlanner::from_state(i).best(0.0) {
+                handle.send(scan.action.to_packet());
+            }
+            return;
+        };
+        if let Some(result) = search.search(&boards) {
+            println!(
+                "mcts: {:?} ({:.2} over {} visits, {} iterations)",
+                result.action, result.value, result.visits, result.iterations
+            );
+            handle.send(result.action.to_packet());
+        }
+    });
+}
+fn pathfind_astar(handle: ChaserHandle, config: &Config) {
     let mut walls = vec![];
     let mut state = TargetState::Searching;
     let mut stuck_counter = 0;
     let mut skip_counter = 0;
+    let mut planner: Option<DStarLite> = None;
+    let mut itinerary: Vec<Pos> = vec![];
     ChaserGame::run_loop(true, handle, |handle| {
-        let (us, opp, opp_elem, size, mut turns_left, map) = {
-            let i = handle.info();
-            #[cfg(feature = "fow")]
-            {
-                (
-                    i.players.us.pos,
-                    i.players.opponent.pos,
-                    i.players.opponent.side.to_elem(),
-                    i.map_size,
-                    i.turns_left,
-                    i.map.clone(),
-                )
-            }
-            #[cfg(not(feature = "fow"))]
-            {
-                (
-                    i.players.us.pos,
-                    Some(i.players.opponent.pos),
-                    i.players.opponent.side.to_elem(),
-                    i.map_size,
-                    i.turns_left,
-                    i.map.clone(),
-                )
+        // a snapshot, so planning below doesn't hold up the protocol thread
+        let i = handle.info();
+        let (us, opp, size, turns_left, fog_of_war) = (
+            i.players.us.pos,
+            i.players.opponent.pos.or_else(|| {
+                i.opponent_belief
+                    .most_likely()
+                    .first()
+                    .filter(|(_, p)| *p >= config.opp_confidence)
+                    .map(|(pos, _)| *pos)
+            }),
+            i.map_size,
+            i.turns_left,
+            i.fog_of_war,
+        );
+        let map = &i.map;
+
+        let scores = (i.players.us.score, i.players.opponent.score);
+        let go_for_opp = |opp: Pos| {
+            if turns_left < config.charge || us.manhattan(opp) < config.opp_range {
+                return true;
             }
+            // if they'd win the race for the hearts anyway, trapping them is our best bet
+            let (ours, theirs) = Territory::new(map, us, opp).secured(scores);
+            ours < theirs
         };
 
-        fn go_for_opp(turns_left: u32, us: Point, opp: Point) -> bool {
-            turns_left < CHARGE || dist(us, opp) < OPP_RANGE
-        }
-
-        if map.deadlocked() || opp.is_some_and(|opp| opp == us) || stuck_counter > 5 {
+        if map.deadlocked()
+            || opp.is_some_and(|opp| opp == us)
+            || stuck_counter > config.stuck_turns
+        {
             println!("deadlocked");
             state = TargetState::FixDeadlock(loop {
-                let around = bounds_ranges(us, size, 2);
-                let x = fastrand::usize(around.0);
-                let y = fastrand::usize(around.1);
-                if map.at(x, y) != Element::Wall && (x, y) != us {
-                    println!("going to {x}, {y}");
-                    break (x, y);
+                let around = bounds_ranges(us, size, config.deadlock_radius);
+                let pos = Pos::new(fastrand::usize(around.0), fastrand::usize(around.1));
+                if map.at(pos) != Element::Wall && pos != us {
+                    println!("going to {pos}");
+                    break pos;
                 }
             });
             stuck_counter = 0;
         }
-        if let Some((_, pos, dir)) = map
-            .around_4(us, size)
-            .iter()
-            .find(|(elem, _, _)| *elem == opp_elem)
-        {
-            println!("placing block on opp at {pos:?} ({dir:?})");
-            handle.send(C2SPacket::PutWall(*dir));
-            return;
+        let kill = match i.players.opponent.pos {
+            Some(opp) => kill::kills(map, us, opp).into_iter().next(),
+            None => kill::kills_by_belief(map, us, &i.opponent_belief, config.opp_confidence)
+                .into_iter()
+                .next()
+                .map(|(kill, _)| kill),
+        };
+        if let Some(kill) = kill.filter(|kill| kill.turns() <= config.kill_turns) {
+            match kill.moves.first() {
+                None => {
+                    println!("placing block at {} ({:?})", kill.wall_at, kill.wall);
+                    handle.send(C2SPacket::PutWall(kill.wall));
+                    return;
+                }
+                Some(&dir)
+                    if us.step(dir).is_some_and(|to| {
+                        safety::safe_to_step(map, to, opp, config.safety_turns)
+                    }) =>
+                {
+                    println!("moving in to block {}", kill.wall_at);
+                    handle.send(C2SPacket::MovePlayer(dir));
+                    return;
+                }
+                Some(_) => (),
+            }
         }
 
-        let scan_chance = if turns_left < CHARGE { 75 } else { 50 };
-        if fastrand::usize(0..100) < scan_chance {
-            random_scan(handle, size, us);
-            turns_left -= 1;
+        let mut route = RoutePlanner::new(map, us, opp, turns_left);
+        route.safety_turns = config.safety_turns;
+        let plan = route.plan();
+        // a scan costs us the step, so it has to beat the hearts that step brings us closer to
+        let scans = ScanPlanner::from_state(&i);
+        let per_turn = plan.stops.len() as f64 / plan.turns().max(1) as f64;
+        let move_value = config.scan_value + per_turn * scans.weights.heart;
+        if let Some(scan) = scans.best(move_value) {
+            println!(
+                "scanning with {:?} (worth {:.1} over {move_value:.1})",
+                scan.action, scan.value
+            );
+            handle.send(scan.action.to_packet());
+            return;
         }
 
-        let hearts = viable_hearts(&map, size, map.hearts_near(us));
         match state {
             TargetState::Searching => {
+                let hearts: Vec<Pos> = plan.stops.iter().map(|s| s.heart).collect();
                 if let Some(opp) = opp
-                    && (hearts.is_empty() || go_for_opp(turns_left, us, opp))
+                    && (hearts.is_empty() || go_for_opp(opp))
                 {
                     println!("running to opp {opp:?}");
                     state = TargetState::Opponent(opp);
-                } else if !hearts.is_empty()
-                    && (cfg!(feature = "fow") || fastrand::usize(0..10) > 3)
-                {
-                    let heart = *hearts.first().unwrap();
+                } else if !hearts.is_empty() && (fog_of_war || fastrand::usize(0..10) > 3) {
+                    let heart = hearts[0];
+                    itinerary = hearts[1..].to_vec();
                     state = TargetState::Heart(heart);
-                    println!("running to heart {heart:?}");
+                    println!("running to heart {heart:?}, then {itinerary:?}");
                 } else {
                     let res = loop {
-                        let x = fastrand::usize(..size.0);
-                        let y = fastrand::usize(..size.1);
-                        if map.at(x, y) != Element::Wall && (x, y) != us {
-                            println!("going to {x}, {y}");
-                            break (x, y);
+                        let pos = Pos::new(fastrand::usize(..size.0), fastrand::usize(..size.1));
+                        if map.at(pos) != Element::Wall && pos != us {
+                            println!("going to {pos}");
+                            break pos;
                         }
                     };
                     state = TargetState::Wandering(res);
@@ -166,10 +367,19 @@ fn pathfind_astar(handle: ChaserHandle) {
             TargetState::Wandering(pos) | TargetState::Heart(pos) | TargetState::Opponent(pos) => {
                 if us == pos {
                     println!("reached destination");
-                    state = TargetState::Searching
+                    let was_heart = matches!(state, TargetState::Heart(_));
+                    state = TargetState::Searching;
+                    // on to the next heart if nobody took it meanwhile
+                    if was_heart && !itinerary.is_empty() {
+                        let next = itinerary.remove(0);
+                        if map.at(next) == Element::Heart {
+                            println!("running on to heart {next:?}");
+                            state = TargetState::Heart(next);
+                        }
+                    }
                 }
                 if let Some(opp) = opp
-                    && go_for_opp(turns_left, us, opp)
+                    && go_for_opp(opp)
                 {
                     println!("running to opp");
                     state = TargetState::Opponent(opp);
@@ -188,25 +398,45 @@ fn pathfind_astar(handle: ChaserHandle) {
         | TargetState::Opponent(target)
         | TargetState::FixDeadlock(target) = state
         {
-            let mut directions =
+            let directions = {
+                let nav = Nav::new(map).blacklist(&walls);
                 if matches!(state, TargetState::Heart(_) | TargetState::Wandering(_)) {
-                    run_astar(&map, us, target, size, &walls, |pos| {
+                    nav.cost(|pos| {
                         if let Some(opp) = opp {
-                            (size.0 + size.1) - dist(pos, opp)
+                            ((size.0 + size.1) - pos.manhattan(opp)) as u32
                         } else {
                             1
                         }
                     })
+                    .path(us, target)
                 } else {
-                    run_astar(&map, us, target, size, &walls, |_| 1)
-                };
+                    // plain step costs, so keep repairing last tick's search instead of
+                    // starting over
+                    let mut known = map.clone();
+                    for &wall in &walls {
+                        known.set(wall, Element::Wall);
+                    }
+                    match &mut planner {
+                        Some(p) if p.goal() == target => {
+                            p.move_to(us);
+                            p.update_map(&known);
+                        }
+                        _ => {
+                            planner =
+                                Some(DStarLite::new(&known, us, target, UnknownCells::Passable))
+                        }
+                    }
+                    planner.as_mut().and_then(DStarLite::path)
+                }
+                .unwrap_or_default()
+            };
             // println!("{directions:?}");
 
-            if let Some(dir) = directions.pop() {
+            if let Some((&dir, rest)) = directions.split_first() {
                 if matches!(state, TargetState::Opponent(_)) {
-                    if directions.is_empty() {
+                    if rest.is_empty() {
                         handle.send(C2SPacket::PutWall(dir));
-                    } else if directions.len() == 1 && skip_counter < 3 {
+                    } else if rest.len() == 1 && skip_counter < config.skip_turns {
                         println!("skipping");
                         skip_counter += 1;
                         handle.send(C2SPacket::Search(Direction::Top));
@@ -215,7 +445,7 @@ fn pathfind_astar(handle: ChaserHandle) {
                         skip_counter = 0;
                     }
                 } else {
-                    if directions.is_empty() && matches!(state, TargetState::Heart(_)) {
+                    if rest.is_empty() && matches!(state, TargetState::Heart(_)) {
                         walls.push(us);
                     }
                     handle.send(C2SPacket::MovePlayer(dir));
@@ -228,137 +458,3 @@ fn pathfind_astar(handle: ChaserHandle) {
         }
     });
 }
-
-fn random_scan(handle: &ChaserHandle, size: Point, pos: Point) {
-    let (half_x, half_y) = (size.0 / 2, size.1 / 2);
-    let area_or_line = fastrand::bool();
-    let dir1_or_dir2 = fastrand::bool();
-    // let (odd_x, odd_y) = (size.0 % 2 == 0, size.1 % 2 == 0);
-    if (0..half_x).contains(&pos.0) {
-        if (0..half_y).contains(&pos.1) {
-            // top left
-            let dir = if dir1_or_dir2 {
-                Direction::Bottom
-            } else {
-                Direction::Right
-            };
-            handle.send(if area_or_line {
-                C2SPacket::Look(dir)
-            } else {
-                C2SPacket::Search(dir)
-            });
-        } else {
-            // top right
-            let dir = if dir1_or_dir2 {
-                Direction::Bottom
-            } else {
-                Direction::Left
-            };
-            handle.send(if area_or_line {
-                C2SPacket::Look(dir)
-            } else {
-                C2SPacket::Search(dir)
-            });
-        }
-    } else if (0..half_y).contains(&pos.1) {
-        // bottom left
-        let dir = if dir1_or_dir2 {
-            Direction::Top
-        } else {
-            Direction::Right
-        };
-        handle.send(if area_or_line {
-            C2SPacket::Look(dir)
-        } else {
-            C2SPacket::Search(dir)
-        });
-    } else {
-        // bottom right
-        let dir = if dir1_or_dir2 {
-            Direction::Top
-        } else {
-            Direction::Left
-        };
-        handle.send(if area_or_line {
-            C2SPacket::Look(dir)
-        } else {
-            C2SPacket::Search(dir)
-        });
-    }
-}
-
-fn run_astar(
-    map: &Map,
-    src: Point,
-    dest: Point,
-    size: Point,
-    blacklisted: &[Point],
-    cost_fn: impl Fn(Point) -> usize,
-) -> Vec<Direction> {
-    let res = astar(
-        &src,
-        |&(x, y)| {
-            let mut options = map.around_4((x, y), size);
-            options.retain(|(elem, pos, _)| *elem != Element::Wall && !blacklisted.contains(pos));
-
-            options
-                .into_iter()
-                .map(|(_, pos, _)| (pos, cost_fn(pos)))
-                .collect::<Vec<_>>()
-        },
-        |&(x, y)| dest.0.abs_diff(x) + dest.1.abs_diff(y),
-        |&p| p == dest,
-    );
-    if let Some((astar_path, _)) = res {
-        into_directions(astar_path)
-    } else {
-        vec![]
-    }
-}
-
-fn into_directions(path: Vec<Point>) -> Vec<Direction> {
-    let mut res = path
-        .iter()
-        .map_windows(|&[&(a_x, a_y), &b]| {
-            if a_x > 0 && b == (a_x - 1, a_y) {
-                Direction::Left
-            } else if a_y > 0 && b == (a_x, a_y - 1) {
-                Direction::Top
-            } else if b == (a_x + 1, a_y) {
-                Direction::Right
-            } else if b == (a_x, a_y + 1) {
-                Direction::Bottom
-            } else {
-                panic!("shouldn't move diagonally!")
-            }
-        })
-        .collect::<Vec<_>>();
-    res.reverse();
-    res
-}
-
-fn viable_hearts(map: &Map, size: Point, mut hearts: Vec<Point>) -> Vec<Point> {
-    hearts.retain(|pos| {
-        let around = map.around_4(*pos, size);
-        let self_gaps = around.iter().fold(0, |acc, (elem, _, _)| {
-            if *elem == Element::Blank || *elem == Element::Heart {
-                acc + 1
-            } else {
-                acc
-            }
-        }) > 1;
-        let around_gaps = around
-            .iter()
-            .flat_map(|(_, pos, _)| map.around_4(*pos, size))
-            .fold(0, |acc, (elem, _, _)| {
-                if elem == Element::Blank || elem == Element::Heart {
-                    acc + 1
-                } else {
-                    acc
-                }
-            })
-            > 1;
-        self_gaps && around_gaps
-    });
-    hearts
-}